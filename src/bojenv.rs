@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use console::Style;
+use serde::Deserialize;
+
+use crate::language;
+use crate::Result;
+
+// How BOJ compiles a Rust language, as listed on https://help.acmicpc.net/language/info
+#[derive(Debug, Clone, Deserialize)]
+pub struct RustEnvironment {
+    pub name: String,
+    // The rustc version BOJ uses
+    pub rustc: String,
+    pub edition: String,
+    pub flags: Vec<String>,
+}
+
+fn environments() -> HashMap<String, RustEnvironment> {
+    let environments_str = include_str!("../assets/rustEnvironments.json");
+    serde_json::from_str(environments_str).unwrap()
+}
+
+// Whether the BOJ language is a Rust one
+pub fn is_rust(language: usize) -> bool {
+    environments().contains_key(&language.to_string())
+}
+
+impl RustEnvironment {
+    pub fn load(language: usize) -> Result<Self> {
+        let mut environments = environments();
+        match environments.remove(&language.to_string()) {
+            Some(environment) => Ok(environment),
+            None => Err(format!(
+                "Error: language {} is not a Rust language. --boj-env supports: {}.",
+                language,
+                supported_languages(&environments)
+            ))?,
+        }
+    }
+}
+
+fn supported_languages(environments: &HashMap<String, RustEnvironment>) -> String {
+    let mut languages = environments.iter().collect::<Vec<_>>();
+    languages.sort_by_key(|(id, _)| id.parse::<usize>().unwrap_or(usize::MAX));
+    languages
+        .iter()
+        .map(|(id, environment)| format!("{} ({})", id, environment.name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Parses the major and minor version from `rustc --version` output, e.g. "rustc 1.65.0 (897e37553 2022-11-02)"
+fn minor_version(version: &str) -> Option<(u32, u32)> {
+    let version = version.split_whitespace().find(|word| word.starts_with(|c: char| c.is_ascii_digit()))?;
+    let mut parts = version.split(|c: char| !c.is_ascii_digit());
+    Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+}
+
+fn rustc_command(environment: &RustEnvironment, pinned: bool) -> Command {
+    if pinned {
+        let mut command = Command::new("rustup");
+        command.args(["run", &environment.rustc, "rustc"]);
+        command
+    } else {
+        Command::new("rustc")
+    }
+}
+
+// Warns if the local rustc is not the version BOJ uses, since std APIs differ between versions.
+fn check_version(environment: &RustEnvironment, pinned: bool) -> Result<()> {
+    let output = rustc_command(environment, pinned)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        if pinned {
+            Err(format!(
+                "Error: could not run rustc {} through rustup. Install it with `rustup toolchain install {}`.",
+                environment.rustc, environment.rustc
+            ))?
+        }
+        Err("Error: could not run rustc.")?
+    }
+    let local = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    let (Some(local_version), Some(boj_version)) = (minor_version(&local), minor_version(&environment.rustc)) else {
+        return Ok(());
+    };
+    let comparison = match local_version.cmp(&boj_version) {
+        std::cmp::Ordering::Equal => return Ok(()),
+        std::cmp::Ordering::Greater => "newer",
+        std::cmp::Ordering::Less => "older",
+    };
+    eprintln!(
+        "{} {} is {} than BOJ's rustc {}, so the solution may not compile the same way on BOJ. \
+         Use --boj-toolchain to build with rustc {} through rustup.",
+        Style::new().yellow().apply_to("Warning:"),
+        local,
+        comparison,
+        environment.rustc,
+        environment.rustc
+    );
+    Ok(())
+}
+
+// Compiles the single source file the way BOJ does, and returns the path of the executable.
+// With `pinned`, the rustc version BOJ uses is run through rustup.
+pub fn build(source: &Path, language: usize, pinned: bool) -> Result<PathBuf> {
+    let environment = RustEnvironment::load(language)?;
+    check_version(&environment, pinned)?;
+    let mut dir = language::build_dir();
+    dir.push("boj-env");
+    fs::create_dir_all(&dir)?;
+    let mut executable = dir;
+    executable.push(format!("Main-{}", language));
+    executable.set_extension(std::env::consts::EXE_EXTENSION);
+    let mut command = rustc_command(&environment, pinned);
+    command
+        .args(["--edition", &environment.edition])
+        .args(&environment.flags)
+        .arg("-o")
+        .arg(&executable)
+        .arg(source);
+    if console::colors_enabled_stderr() {
+        command.args(["--color", "always"]);
+    }
+    let output = command.stdin(Stdio::null()).output()?;
+    if !output.status.success() {
+        eprint!("{}", String::from_utf8_lossy(&output.stderr));
+        Err(format!(
+            "Error: {} does not compile as {} on BOJ (rustc {}, edition {}).",
+            source.display(),
+            environment.name,
+            environment.rustc,
+            environment.edition
+        ))?
+    }
+    Ok(executable)
+}
@@ -0,0 +1,858 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use proc_macro2::{LineColumn, Span, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+use syn::{Attribute, ImplItem, Item, ItemMod, ItemUse, UseTree};
+
+use crate::metadata::CurrentPackage;
+use crate::Result;
+
+// Bundles a Rust solution into a single file, as BOJ accepts only one source file:
+// `mod foo;` declarations are replaced with the contents of their files, and the local library crates
+// the solution uses are appended as modules. Tests and library modules the solution never mentions are left out.
+// The code is edited as text, so that the formatting and comments of the original are kept.
+pub fn bundle(source: &Path) -> Result<Bundled> {
+    // Library crates are only bundled from within a package
+    let libs = match CurrentPackage::load() {
+        Ok(package) => package.local_libs()?,
+        Err(_) => vec![],
+    };
+    let names = libs.iter().map(|lib| lib.name.clone()).collect::<HashSet<_>>();
+
+    // Macros exported by the libraries live at the crate root, so paths to them are rewritten differently
+    let mut macros = HashSet::new();
+    for lib in &libs {
+        let mut expander = Expander::new(Some(&lib.name), &names, &macros);
+        expander.expand_crate(&lib.src_path)?;
+        macros.extend(expander.exported_macros);
+    }
+
+    let mut expander = Expander::new(None, &names, &macros);
+    let mut bundled = expander.expand_crate(source)?;
+    let mut pending = expander.used_libs.into_iter().collect::<Vec<_>>();
+    let mut expanded_libs = vec![];
+    let mut used = HashSet::new();
+    while let Some(name) = pending.pop() {
+        if !used.insert(name.clone()) {
+            continue;
+        }
+        let lib = libs.iter().find(|lib| lib.name == name).unwrap();
+        let mut expander = Expander::new(Some(&lib.name), &names, &macros);
+        let text = expander.expand_crate(&lib.src_path)?;
+        pending.extend(expander.used_libs);
+        expanded_libs.push((name, text));
+    }
+    // Keep the order of the libraries stable
+    expanded_libs.sort();
+
+    let mut solution_idents = HashSet::new();
+    collect_idents(TokenStream::from_str(&bundled)?, &mut solution_idents);
+    let mut lib_idents = vec![];
+    for (_, text) in &expanded_libs {
+        let mut idents = HashSet::new();
+        collect_idents(TokenStream::from_str(text)?, &mut idents);
+        lib_idents.push(idents);
+    }
+    let mut bundled_libs = vec![];
+    for (i, (name, text)) in expanded_libs.into_iter().enumerate() {
+        // A library's modules may be used by the solution or by the other libraries
+        let mut mentioned = solution_idents.clone();
+        for (j, idents) in lib_idents.iter().enumerate() {
+            if i != j {
+                mentioned.extend(idents.iter().cloned());
+            }
+        }
+        let text = prune_modules(&text, &mentioned)?;
+        if !bundled.ends_with('\n') {
+            bundled.push('\n');
+        }
+        bundled.push_str(&format!("\n#[allow(unused)]\nmod {} {{\n{}}}\n", name, text));
+        bundled_libs.push(name);
+    }
+    Ok(Bundled {
+        source: bundled,
+        libs: bundled_libs,
+    })
+}
+
+pub struct Bundled {
+    pub source: String,
+    // The top-level modules holding the bundled library crates
+    pub libs: Vec<String>,
+}
+
+// Converts the line-column positions of spans in a source file to byte offsets
+pub struct Offsets {
+    line_starts: Vec<usize>,
+    source: String,
+}
+
+impl Offsets {
+    pub fn new(source: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            line_starts,
+            source: source.to_string(),
+        }
+    }
+
+    pub fn offset(&self, position: LineColumn) -> usize {
+        let start = self.line_starts[position.line - 1];
+        // Columns count chars, not bytes
+        self.source[start..]
+            .char_indices()
+            .nth(position.column)
+            .map_or(self.source.len(), |(i, _)| start + i)
+    }
+
+    pub fn range(&self, span: Span) -> Range<usize> {
+        self.offset(span.start())..self.offset(span.end())
+    }
+
+    // The range of a removed item, including its whole lines if nothing else is on them
+    pub fn removal_range(&self, span: Span) -> Range<usize> {
+        let Range { mut start, mut end } = self.range(span);
+        let before = self.source[..start].trim_end_matches([' ', '\t']);
+        let after = self.source[end..].trim_start_matches([' ', '\t']);
+        if before.is_empty() || before.ends_with('\n') {
+            if after.starts_with('\n') {
+                start = before.len();
+                end = self.source.len() - after.len() + 1;
+            } else if after.starts_with("\r\n") {
+                start = before.len();
+                end = self.source.len() - after.len() + 2;
+            }
+        }
+        start..end
+    }
+}
+
+pub fn apply_edits(source: &str, mut edits: Vec<(Range<usize>, String)>) -> String {
+    // Insertions at the same position are applied in the order they were made
+    edits.sort_by_key(|(range, _)| (range.start, range.end));
+    let mut result = String::with_capacity(source.len());
+    let mut last = 0;
+    for (range, text) in edits {
+        if range.start < last {
+            continue;
+        }
+        result.push_str(&source[last..range.start]);
+        result.push_str(&text);
+        last = range.end;
+    }
+    result.push_str(&source[last..]);
+    result
+}
+
+pub fn parse(source: &str, path: &Path) -> Result<syn::File> {
+    match syn::parse_file(source) {
+        Ok(file) => Ok(file),
+        Err(e) => {
+            let start = e.span().start();
+            Err(format!(
+                "Error: could not parse {}:{}:{}: {}",
+                path.display(),
+                start.line,
+                start.column + 1,
+                e
+            ))?
+        }
+    }
+}
+
+fn item_attrs(item: &Item) -> &[Attribute] {
+    match item {
+        Item::Const(item) => &item.attrs,
+        Item::Enum(item) => &item.attrs,
+        Item::ExternCrate(item) => &item.attrs,
+        Item::Fn(item) => &item.attrs,
+        Item::ForeignMod(item) => &item.attrs,
+        Item::Impl(item) => &item.attrs,
+        Item::Macro(item) => &item.attrs,
+        Item::Mod(item) => &item.attrs,
+        Item::Static(item) => &item.attrs,
+        Item::Struct(item) => &item.attrs,
+        Item::Trait(item) => &item.attrs,
+        Item::TraitAlias(item) => &item.attrs,
+        Item::Type(item) => &item.attrs,
+        Item::Union(item) => &item.attrs,
+        Item::Use(item) => &item.attrs,
+        _ => &[],
+    }
+}
+
+fn impl_item_attrs(item: &ImplItem) -> &[Attribute] {
+    match item {
+        ImplItem::Const(item) => &item.attrs,
+        ImplItem::Fn(item) => &item.attrs,
+        ImplItem::Type(item) => &item.attrs,
+        ImplItem::Macro(item) => &item.attrs,
+        _ => &[],
+    }
+}
+
+// `#[test]` and `#[cfg(test)]` items are never compiled in a submission
+fn is_test(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path().is_ident("test")
+            || (attr.path().is_ident("cfg")
+                && attr
+                    .meta
+                    .require_list()
+                    .is_ok_and(|list| list.tokens.to_string() == "test"))
+    })
+}
+
+fn path_attr(attrs: &[Attribute]) -> Option<String> {
+    attrs.iter().find_map(|attr| match &attr.meta {
+        syn::Meta::NameValue(meta) if meta.path.is_ident("path") => match &meta.value {
+            syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(path),
+                ..
+            }) => Some(path.value()),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+// Expands the modules of one crate, rewriting paths so that it works as a part of the bundle
+struct Expander<'a> {
+    // Set for a library crate, which is bundled as `mod <name>` at the crate root
+    crate_name: Option<&'a str>,
+    libs: &'a HashSet<String>,
+    lib_macros: &'a HashSet<String>,
+    used_libs: BTreeSet<String>,
+    exported_macros: Vec<String>,
+}
+
+impl<'a> Expander<'a> {
+    fn new(
+        crate_name: Option<&'a str>,
+        libs: &'a HashSet<String>,
+        lib_macros: &'a HashSet<String>,
+    ) -> Self {
+        Self {
+            crate_name,
+            libs,
+            lib_macros,
+            used_libs: BTreeSet::new(),
+            exported_macros: vec![],
+        }
+    }
+
+    fn expand_crate(&mut self, root: &Path) -> Result<String> {
+        let dir = root.parent().unwrap_or(Path::new("."));
+        self.expand_file(root, dir.to_path_buf(), dir.to_path_buf(), 0)
+    }
+
+    // `mod_dir` is where `mod foo;` looks for foo.rs, and `path_dir` is what `#[path]` is relative to.
+    fn expand_file(
+        &mut self,
+        path: &Path,
+        mod_dir: PathBuf,
+        path_dir: PathBuf,
+        depth: usize,
+    ) -> Result<String> {
+        let source = match fs::read_to_string(path) {
+            Ok(source) => source,
+            Err(e) => Err(format!("Error: could not read {}: {}", path.display(), e))?,
+        };
+        let source = source.trim_start_matches('\u{feff}');
+        let file = parse(source, path)?;
+        let mut visitor = FileVisitor {
+            expander: self,
+            offsets: Offsets::new(source),
+            edits: vec![],
+            mod_dir,
+            path_dir,
+            depth,
+            error: None,
+        };
+        visitor.visit_file(&file);
+        if let Some(e) = visitor.error {
+            return Err(e);
+        }
+        let edits = visitor.edits;
+        Ok(apply_edits(source, edits))
+    }
+
+    fn is_lib(&self, ident: &proc_macro2::Ident) -> bool {
+        self.libs.contains(&ident.to_string())
+    }
+}
+
+struct FileVisitor<'e, 'a> {
+    expander: &'e mut Expander<'a>,
+    offsets: Offsets,
+    edits: Vec<(Range<usize>, String)>,
+    mod_dir: PathBuf,
+    path_dir: PathBuf,
+    // How deep the current module is below the crate root
+    depth: usize,
+    error: Option<Box<dyn std::error::Error>>,
+}
+
+impl FileVisitor<'_, '_> {
+    fn remove(&mut self, span: Span) {
+        let range = self.offsets.removal_range(span);
+        self.edits.push((range, String::new()));
+    }
+
+    fn replace(&mut self, span: Span, text: impl Into<String>) {
+        let range = self.offsets.range(span);
+        self.edits.push((range, text.into()));
+    }
+
+    fn insert_before(&mut self, span: Span, text: impl Into<String>) {
+        let start = self.offsets.offset(span.start());
+        self.edits.push((start..start, text.into()));
+    }
+
+    fn insert_after(&mut self, span: Span, text: impl Into<String>) {
+        let end = self.offsets.offset(span.end());
+        self.edits.push((end..end, text.into()));
+    }
+
+    fn expand_mod(&mut self, item: &ItemMod) -> Result<()> {
+        let name = item.ident.to_string();
+        let path_attr = path_attr(&item.attrs);
+        let Some(semi) = &item.semi else {
+            // An inline module; its `mod foo;` declarations are looked for in a directory named after it
+            let dir = self.mod_dir.join(path_attr.as_deref().unwrap_or(&name));
+            let mod_dir = std::mem::replace(&mut self.mod_dir, dir.clone());
+            let path_dir = std::mem::replace(&mut self.path_dir, dir);
+            self.depth += 1;
+            visit::visit_item_mod(self, item);
+            self.depth -= 1;
+            self.mod_dir = mod_dir;
+            self.path_dir = path_dir;
+            return Ok(());
+        };
+        let (file, mod_dir) = match path_attr {
+            Some(path) => {
+                let file = self.path_dir.join(path);
+                let dir = file.parent().unwrap().to_path_buf();
+                (file, dir)
+            }
+            None => {
+                let file = self.mod_dir.join(format!("{}.rs", name));
+                let mod_rs = self.mod_dir.join(&name).join("mod.rs");
+                if file.exists() {
+                    (file, self.mod_dir.join(&name))
+                } else if mod_rs.exists() {
+                    (mod_rs, self.mod_dir.join(&name))
+                } else {
+                    Err(format!(
+                        "Error: could not find module `{}`: neither {} nor {} exists.",
+                        name,
+                        file.display(),
+                        mod_rs.display()
+                    ))?
+                }
+            }
+        };
+        let path_dir = file.parent().unwrap().to_path_buf();
+        let mut text = self
+            .expander
+            .expand_file(&file, mod_dir, path_dir, self.depth + 1)?;
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+        self.replace(semi.span, format!(" {{\n{}}}", text));
+        Ok(())
+    }
+
+    // Paths to a library crate go through the module it is bundled as,
+    // and a library's paths to its own crate root go to that module.
+    fn rewrite_path(&mut self, path: &syn::Path) {
+        if path.leading_colon.is_some() {
+            return;
+        }
+        let first = &path.segments[0].ident;
+        if let Some(name) = self.expander.crate_name {
+            if first == "crate" && path.segments.len() > 1 {
+                self.insert_after(first.span(), format!("::{}", name));
+            }
+        }
+        if path.segments.len() > 1 && self.expander.is_lib(first) {
+            self.expander.used_libs.insert(first.to_string());
+            self.insert_before(first.span(), "crate::");
+        }
+    }
+
+    // Macros are not parsed, so paths in them are found by looking for `<ident>::`
+    fn rewrite_tokens(&mut self, tokens: &TokenStream) {
+        let tokens = tokens.clone().into_iter().collect::<Vec<_>>();
+        let is_punct = |i: usize, c: char| matches!(tokens.get(i), Some(TokenTree::Punct(p)) if p.as_char() == c);
+        let is_path_sep = |i: usize| is_punct(i, ':') && is_punct(i + 1, ':');
+        for (i, token) in tokens.iter().enumerate() {
+            let ident = match token {
+                TokenTree::Group(group) => {
+                    self.rewrite_tokens(&group.stream());
+                    continue;
+                }
+                TokenTree::Ident(ident) => ident,
+                _ => continue,
+            };
+            if !is_path_sep(i + 1) || (i >= 2 && is_path_sep(i - 2)) {
+                continue;
+            }
+            if ident == "crate" {
+                // Also covers `$crate`
+                if let Some(name) = self.expander.crate_name {
+                    self.insert_after(ident.span(), format!("::{}", name));
+                }
+            } else if self.expander.is_lib(ident) && !(i >= 1 && is_punct(i - 1, '$')) {
+                self.expander.used_libs.insert(ident.to_string());
+                let calls_exported_macro = matches!(tokens.get(i + 3), Some(TokenTree::Ident(m)) if self.expander.lib_macros.contains(&m.to_string()))
+                    && is_punct(i + 4, '!');
+                if calls_exported_macro {
+                    self.replace(ident.span(), "crate");
+                } else {
+                    self.insert_before(ident.span(), "crate::");
+                }
+            }
+        }
+    }
+
+    fn rewrite_use(&mut self, item: &ItemUse) {
+        let mut tree = item.tree.clone();
+        let mut macros = vec![];
+        if !self.rewrite_use_tree(&mut tree, &mut macros) {
+            return;
+        }
+        let mut items = vec![];
+        if !is_empty(&tree) {
+            items.push(Item::Use(ItemUse {
+                tree,
+                ..item.clone()
+            }));
+        }
+        // Exported macros are already in scope at the crate root, and elsewhere they are imported from there
+        if self.depth > 0 {
+            for leaf in macros {
+                items.push(Item::Use(ItemUse {
+                    tree: syn::parse_quote!(crate::#leaf),
+                    ..item.clone()
+                }));
+            }
+        }
+        let file = syn::File {
+            shebang: None,
+            attrs: vec![],
+            items,
+        };
+        let text = prettyplease::unparse(&file);
+        if text.trim().is_empty() {
+            self.remove(item.span());
+            return;
+        }
+        // Keep the indentation of the original item on every line
+        let start = self.offsets.range(item.span()).start;
+        let line_start = self.offsets.source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let indent = &self.offsets.source[line_start..start];
+        let indent = if indent.trim().is_empty() { indent } else { "" };
+        let text = text.trim_end().replace('\n', &format!("\n{}", indent));
+        self.replace(item.span(), text);
+    }
+
+    // Returns whether the tree imports from a library crate, after rewriting it.
+    // Imports of exported macros are taken out into `macros`.
+    fn rewrite_use_tree(&mut self, tree: &mut UseTree, macros: &mut Vec<UseTree>) -> bool {
+        match tree {
+            UseTree::Path(path) if self.expander.is_lib(&path.ident) => {
+                self.expander.used_libs.insert(path.ident.to_string());
+                take_macros(&mut path.tree, self.expander.lib_macros, macros);
+                let inner = tree.clone();
+                *tree = syn::parse_quote!(crate::#inner);
+                true
+            }
+            UseTree::Name(name) if self.expander.is_lib(&name.ident) => {
+                self.expander.used_libs.insert(name.ident.to_string());
+                // The module is already in scope at the crate root
+                if self.depth == 0 {
+                    *tree = UseTree::Group(syn::UseGroup {
+                        brace_token: Default::default(),
+                        items: Default::default(),
+                    });
+                } else {
+                    let inner = tree.clone();
+                    *tree = syn::parse_quote!(crate::#inner);
+                }
+                true
+            }
+            UseTree::Rename(rename) if self.expander.is_lib(&rename.ident) => {
+                self.expander.used_libs.insert(rename.ident.to_string());
+                let inner = tree.clone();
+                *tree = syn::parse_quote!(crate::#inner);
+                true
+            }
+            UseTree::Group(group) => {
+                let mut changed = false;
+                for item in group.items.iter_mut() {
+                    changed |= self.rewrite_use_tree(item, macros);
+                }
+                changed
+            }
+            _ => false,
+        }
+    }
+
+    fn rewrite_crate_use(&mut self, tree: &UseTree) {
+        match tree {
+            UseTree::Path(path) if path.ident == "crate" => {
+                let name = self.expander.crate_name.unwrap();
+                self.insert_after(path.ident.span(), format!("::{}", name));
+            }
+            UseTree::Group(group) => {
+                for item in &group.items {
+                    self.rewrite_crate_use(item);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn is_empty(tree: &UseTree) -> bool {
+    match tree {
+        UseTree::Path(path) => is_empty(&path.tree),
+        UseTree::Group(group) => group.items.iter().all(is_empty),
+        _ => false,
+    }
+}
+
+// Moves the imports of exported macros out of the tree
+fn take_macros(tree: &mut UseTree, lib_macros: &HashSet<String>, macros: &mut Vec<UseTree>) {
+    match tree {
+        UseTree::Name(syn::UseName { ident }) | UseTree::Rename(syn::UseRename { ident, .. })
+            if lib_macros.contains(&ident.to_string()) =>
+        {
+            macros.push(tree.clone());
+            *tree = UseTree::Group(syn::UseGroup {
+                brace_token: Default::default(),
+                items: Default::default(),
+            });
+        }
+        UseTree::Path(path) => take_macros(&mut path.tree, lib_macros, macros),
+        UseTree::Group(group) => {
+            for item in group.items.iter_mut() {
+                take_macros(item, lib_macros, macros);
+            }
+            let items = std::mem::take(&mut group.items);
+            group.items = items.into_iter().filter(|item| !is_empty(item)).collect();
+        }
+        _ => {}
+    }
+}
+
+impl<'ast> Visit<'ast> for FileVisitor<'_, '_> {
+    fn visit_item(&mut self, item: &'ast Item) {
+        if self.error.is_some() {
+            return;
+        }
+        if is_test(item_attrs(item)) {
+            self.remove(item.span());
+            return;
+        }
+        visit::visit_item(self, item);
+    }
+
+    fn visit_impl_item(&mut self, item: &'ast ImplItem) {
+        if is_test(impl_item_attrs(item)) {
+            self.remove(item.span());
+            return;
+        }
+        visit::visit_impl_item(self, item);
+    }
+
+    fn visit_item_mod(&mut self, item: &'ast ItemMod) {
+        if let Err(e) = self.expand_mod(item) {
+            self.error = Some(e);
+        }
+    }
+
+    fn visit_item_extern_crate(&mut self, item: &'ast syn::ItemExternCrate) {
+        if self.expander.is_lib(&item.ident) {
+            self.expander.used_libs.insert(item.ident.to_string());
+            self.remove(item.span());
+        }
+    }
+
+    fn visit_item_use(&mut self, item: &'ast ItemUse) {
+        if self.expander.crate_name.is_some() {
+            self.rewrite_crate_use(&item.tree);
+        }
+        self.rewrite_use(item);
+    }
+
+    fn visit_item_macro(&mut self, item: &'ast syn::ItemMacro) {
+        let exported = item.attrs.iter().any(|attr| attr.path().is_ident("macro_export"));
+        if let (true, Some(ident)) = (exported, &item.ident) {
+            self.expander.exported_macros.push(ident.to_string());
+        }
+        visit::visit_item_macro(self, item);
+    }
+
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        let path = &mac.path;
+        let first = &path.segments[0].ident;
+        let last = &path.segments.last().unwrap().ident;
+        if path.leading_colon.is_none()
+            && path.segments.len() > 1
+            && self.expander.is_lib(first)
+            && self.expander.lib_macros.contains(&last.to_string())
+        {
+            self.expander.used_libs.insert(first.to_string());
+            self.replace(path.span(), format!("crate::{}", last));
+        } else {
+            self.rewrite_path(path);
+        }
+        self.rewrite_tokens(&mac.tokens);
+    }
+
+    fn visit_path(&mut self, path: &'ast syn::Path) {
+        self.rewrite_path(path);
+        visit::visit_path(self, path);
+    }
+}
+
+pub fn collect_idents(tokens: TokenStream, idents: &mut HashSet<String>) {
+    for token in tokens {
+        match token {
+            TokenTree::Ident(ident) => {
+                idents.insert(ident.to_string());
+            }
+            TokenTree::Group(group) => collect_idents(group.stream(), idents),
+            _ => {}
+        }
+    }
+}
+
+// The names of the macros defined with `macro_rules!` in the code
+fn defined_macros(tokens: TokenStream, macros: &mut Vec<String>) {
+    let tokens = tokens.into_iter().collect::<Vec<_>>();
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            TokenTree::Ident(ident) if ident == "macro_rules" => {
+                if let (Some(TokenTree::Punct(bang)), Some(TokenTree::Ident(name))) =
+                    (tokens.get(i + 1), tokens.get(i + 2))
+                {
+                    if bang.as_char() == '!' {
+                        macros.push(name.to_string());
+                    }
+                }
+            }
+            TokenTree::Group(group) => defined_macros(group.stream(), macros),
+            _ => {}
+        }
+    }
+}
+
+// Removes the top-level modules of a library that are not mentioned by name anywhere,
+// neither directly nor through the macros they define.
+fn prune_modules(source: &str, mentioned: &HashSet<String>) -> Result<String> {
+    let file = parse(source, Path::new("<bundled library>"))?;
+    let offsets = Offsets::new(source);
+    let mut mentioned = mentioned.clone();
+    let mut modules = vec![];
+    for item in &file.items {
+        match item {
+            Item::Mod(module) => {
+                let mut names = vec![module.ident.to_string()];
+                defined_macros(module.to_token_stream(), &mut names);
+                modules.push((module, names));
+            }
+            item => collect_idents(item.to_token_stream(), &mut mentioned),
+        }
+    }
+    // A module mentioned by a kept module is kept as well
+    loop {
+        let (kept, rest): (Vec<_>, Vec<_>) = modules
+            .into_iter()
+            .partition(|(_, names)| names.iter().any(|name| mentioned.contains(name)));
+        modules = rest;
+        if kept.is_empty() {
+            break;
+        }
+        for (module, _) in kept {
+            collect_idents(module.to_token_stream(), &mut mentioned);
+        }
+    }
+    let edits = modules
+        .into_iter()
+        .map(|(module, _)| (offsets.removal_range(module.span()), String::new()))
+        .collect();
+    Ok(apply_edits(source, edits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writes the files of a crate into a fresh directory, returning the path of its root file
+    fn write_crate(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cargo-boj-{}-test-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        for (path, text) in files {
+            let path = dir.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, text).unwrap();
+        }
+        dir.join(files[0].0)
+    }
+
+    struct Expanded {
+        text: String,
+        used_libs: Vec<String>,
+        exported_macros: Vec<String>,
+    }
+
+    fn expand(root: &Path, crate_name: Option<&str>, libs: &[&str], macros: &[&str]) -> Expanded {
+        let libs = libs.iter().map(|lib| lib.to_string()).collect();
+        let macros = macros.iter().map(|name| name.to_string()).collect();
+        let mut expander = Expander::new(crate_name, &libs, &macros);
+        let text = expander.expand_crate(root).unwrap();
+        let _ = fs::remove_dir_all(root.parent().unwrap());
+        Expanded {
+            text,
+            used_libs: expander.used_libs.into_iter().collect(),
+            exported_macros: expander.exported_macros,
+        }
+    }
+
+    #[test]
+    fn inlines_module_files() {
+        let root = write_crate(
+            "modules",
+            &[
+                ("main.rs", "mod a;\nmod b;\n#[path = \"other/c_impl.rs\"]\nmod c;\n\nfn main() {}\n"),
+                ("a.rs", "pub mod inner;\n"),
+                ("a/inner.rs", "pub fn in_a() {}\n"),
+                ("b/mod.rs", "pub fn in_b() {}\n"),
+                ("other/c_impl.rs", "pub fn in_c() {}\n"),
+            ],
+        );
+        let expanded = expand(&root, None, &[], &[]);
+        assert_eq!(
+            expanded.text,
+            "mod a {\npub mod inner {\npub fn in_a() {}\n}\n}\nmod b {\npub fn in_b() {}\n}\n\
+             #[path = \"other/c_impl.rs\"]\nmod c {\npub fn in_c() {}\n}\n\nfn main() {}\n"
+        );
+    }
+
+    #[test]
+    fn reports_missing_module_files() {
+        let root = write_crate("missing", &[("main.rs", "mod gone;\n")]);
+        let libs = HashSet::new();
+        let macros = HashSet::new();
+        let mut expander = Expander::new(None, &libs, &macros);
+        let e = expander.expand_crate(&root).unwrap_err();
+        let _ = fs::remove_dir_all(root.parent().unwrap());
+        assert!(e.to_string().starts_with("Error: could not find module `gone`"));
+    }
+
+    #[test]
+    fn rewrites_crate_paths_in_a_library() {
+        let root = write_crate(
+            "crate-paths",
+            &[
+                ("lib.rs", "pub mod seg;\npub use crate::seg::SegTree;\n"),
+                (
+                    "seg.rs",
+                    "use crate::util::{max, min};\n\
+                     pub struct SegTree;\n\
+                     pub fn build() -> crate::seg::SegTree {\n    SegTree\n}\n",
+                ),
+            ],
+        );
+        let expanded = expand(&root, Some("alg"), &["alg"], &[]);
+        assert!(expanded.text.contains("pub use crate::alg::seg::SegTree;"));
+        assert!(expanded.text.contains("use crate::alg::util::{max, min};"));
+        assert!(expanded.text.contains("pub fn build() -> crate::alg::seg::SegTree {"));
+    }
+
+    #[test]
+    fn rewrites_crate_in_exported_macros() {
+        let root = write_crate(
+            "macros",
+            &[
+                (
+                    "lib.rs",
+                    "pub mod io;\n\n#[macro_export]\nmacro_rules! input {\n    () => {\n        $crate::io::read()\n    };\n}\n\n\
+                     macro_rules! local {\n    () => {};\n}\n",
+                ),
+                ("io.rs", "pub fn read() -> String {\n    String::new()\n}\n"),
+            ],
+        );
+        let expanded = expand(&root, Some("alg"), &["alg"], &[]);
+        assert!(expanded.text.contains("$crate::alg::io::read()"));
+        assert_eq!(expanded.exported_macros, ["input"]);
+    }
+
+    #[test]
+    fn rewrites_imports_of_exported_macros() {
+        let root = write_crate(
+            "macro-imports",
+            &[
+                (
+                    "main.rs",
+                    "use alg::input;\nuse alg::seg::SegTree;\n\nmod sub {\n    use alg::{input, seg::Lazy};\n}\n\n\
+                     fn main() {\n    let n: usize = alg::input!();\n    let m = alg::seg::len();\n}\n",
+                ),
+            ],
+        );
+        let expanded = expand(&root, None, &["alg"], &["input"]);
+        assert_eq!(
+            expanded.text,
+            "use crate::alg::seg::SegTree;\n\nmod sub {\n    use crate::alg::seg::Lazy;\n    use crate::input;\n}\n\n\
+             fn main() {\n    let n: usize = crate::input!();\n    let m = crate::alg::seg::len();\n}\n"
+        );
+        assert_eq!(expanded.used_libs, ["alg"]);
+    }
+
+    #[test]
+    fn removes_tests() {
+        let root = write_crate(
+            "tests",
+            &[(
+                "main.rs",
+                "fn main() {}\n\nstruct S;\n\nimpl S {\n    fn f() {}\n\n    #[cfg(test)]\n    fn helper() {}\n}\n\n\
+                 #[test]\nfn works() {}\n\n#[cfg(test)]\nmod tests;\n",
+            )],
+        );
+        let expanded = expand(&root, None, &[], &[]);
+        assert_eq!(
+            expanded.text,
+            "fn main() {}\n\nstruct S;\n\nimpl S {\n    fn f() {}\n\n}\n\n\n"
+        );
+    }
+
+    #[test]
+    fn prunes_unmentioned_modules() {
+        let source = "pub mod seg {\n    pub struct SegTree(crate::alg::math::Monoid);\n}\n\
+                      pub mod math {\n    pub struct Monoid;\n}\n\
+                      pub mod io {\n    #[macro_export]\n    macro_rules! input {\n        () => {};\n    }\n}\n\
+                      pub mod graph {\n    pub fn dijkstra() {}\n}\n";
+        let mentioned = ["SegTree", "seg", "input"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        let pruned = prune_modules(source, &mentioned).unwrap();
+        assert!(pruned.contains("pub mod seg"));
+        // Kept because seg mentions it
+        assert!(pruned.contains("pub mod math"));
+        // Kept for the macro it defines
+        assert!(pruned.contains("pub mod io"));
+        assert!(!pruned.contains("graph"));
+    }
+}
@@ -0,0 +1,87 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::datastore::CustomCase;
+use crate::Result;
+
+// Opens the user's editor on an empty temp file and returns what was written to it.
+fn read_from_editor(what: &str) -> Result<String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| {
+            if cfg!(target_os = "windows") {
+                "notepad".to_string()
+            } else {
+                "vi".to_string()
+            }
+        });
+    let mut path = std::env::temp_dir();
+    path.push(format!("cargo-boj-{}-{}.txt", std::process::id(), what));
+    fs::write(&path, "")?;
+    // $EDITOR may contain arguments, so let the shell split it
+    let status = if cfg!(target_os = "windows") {
+        Command::new("cmd")
+            .arg("/C")
+            .arg(format!("{} \"{}\"", editor, path.display()))
+            .status()?
+    } else {
+        Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$1\"", editor))
+            .arg("sh")
+            .arg(&path)
+            .status()?
+    };
+    let content = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+    if !status.success() {
+        Err(format!("Error: editor `{}` exited with {}.", editor, status))?
+    }
+    Ok(content?)
+}
+
+fn read_source(path: Option<PathBuf>, what: &str) -> Result<String> {
+    match path {
+        Some(path) => fs::read_to_string(&path)
+            .map_err(|e| format!("Error: failed to read {}: {}", path.display(), e).into()),
+        None => {
+            println!("Opening editor for the {}...", what);
+            read_from_editor(what)
+        }
+    }
+}
+
+pub fn add(
+    problem_id: &str,
+    name: Option<String>,
+    input: Option<PathBuf>,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let input = read_source(input, "input")?;
+    let output = read_source(output, "output")?;
+    if input.trim().is_empty() {
+        Err("Error: the input is empty. Aborting.")?
+    }
+    let name = name.unwrap_or_else(|| CustomCase::next_name(problem_id));
+    let case = CustomCase {
+        name,
+        input,
+        output,
+    };
+    let path = case.save(problem_id)?;
+    println!("Custom case `{}` saved to {}.", case.name, path.display());
+    Ok(())
+}
+
+pub fn list(problem_id: &str) -> Result<()> {
+    let cases = CustomCase::load_all(problem_id);
+    if cases.is_empty() {
+        println!("No custom cases for problem {}.", problem_id);
+    }
+    for case in cases {
+        let first_line = case.input.lines().next().unwrap_or_default();
+        println!("{}: {}", case.name, first_line);
+    }
+    Ok(())
+}
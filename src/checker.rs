@@ -0,0 +1,175 @@
+use std::fs;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::compare::{float_eq, Compare};
+use crate::test::shell_command;
+use crate::Result;
+
+// Checkers decide whether an output is accepted when it may differ from the expected output,
+// e.g. for Special Judge problems.
+#[derive(Debug, Clone)]
+pub enum Checker {
+    // Tokens are compared as numbers, within absolute or relative error
+    Float { abs: f64, rel: f64 },
+    // Whitespace-insensitive comparison of tokens
+    Token,
+    // Lines are compared as a multiset
+    Unordered,
+    CaseInsensitive,
+    // A testlib-style checker: `<cmd> <input> <output> <answer>`, exit code 0 means accepted
+    External(String),
+}
+
+const DEFAULT_EPS: f64 = 1e-6;
+
+impl FromStr for Checker {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let parse_eps = |eps: &str| {
+            eps.parse::<f64>()
+                .map_err(|_| format!("invalid epsilon `{}` for float checker", eps))
+        };
+        match s.split(':').collect::<Vec<_>>()[..] {
+            ["float"] => Ok(Self::Float {
+                abs: DEFAULT_EPS,
+                rel: DEFAULT_EPS,
+            }),
+            ["float", eps] => {
+                let eps = parse_eps(eps)?;
+                Ok(Self::Float { abs: eps, rel: eps })
+            }
+            ["float", abs, rel] => Ok(Self::Float {
+                abs: parse_eps(abs)?,
+                rel: parse_eps(rel)?,
+            }),
+            ["token"] => Ok(Self::Token),
+            ["unordered"] => Ok(Self::Unordered),
+            ["nocase"] => Ok(Self::CaseInsensitive),
+            _ => Ok(Self::External(s.to_string())),
+        }
+    }
+}
+
+pub struct CheckResult {
+    pub passed: bool,
+    // Explanation of the verdict, if the checker gave one
+    pub message: Option<String>,
+}
+
+impl CheckResult {
+    fn new(passed: bool) -> Self {
+        Self {
+            passed,
+            message: None,
+        }
+    }
+}
+
+fn trimmed_lines(s: &str) -> Vec<&str> {
+    s.trim_end().lines().map(|l| l.trim_end()).collect()
+}
+
+impl Checker {
+    pub fn check(&self, input: &str, expected: &str, actual: &str) -> Result<CheckResult> {
+        let result = match self {
+            Checker::Float { abs, rel } => {
+                let expected = expected.split_whitespace().collect::<Vec<_>>();
+                let actual = actual.split_whitespace().collect::<Vec<_>>();
+                if expected.len() != actual.len() {
+                    return Ok(CheckResult {
+                        passed: false,
+                        message: Some(format!(
+                            "expected {} tokens, found {}",
+                            expected.len(),
+                            actual.len()
+                        )),
+                    });
+                }
+                let mismatch = expected.iter().zip(&actual).position(|(e, a)| {
+                    match (e.parse::<f64>(), a.parse::<f64>()) {
+                        (Ok(e), Ok(a)) => !float_eq(a, e, *abs, *rel),
+                        _ => e != a,
+                    }
+                });
+                match mismatch {
+                    Some(i) => CheckResult {
+                        passed: false,
+                        message: Some(format!(
+                            "token {}: expected `{}`, found `{}`",
+                            i + 1,
+                            expected[i],
+                            actual[i]
+                        )),
+                    },
+                    None => CheckResult::new(true),
+                }
+            }
+            Checker::Token => CheckResult::new(Compare::Token.matches(actual, expected)),
+            Checker::Unordered => {
+                let mut expected = trimmed_lines(expected);
+                let mut actual = trimmed_lines(actual);
+                expected.sort_unstable();
+                actual.sort_unstable();
+                CheckResult::new(expected == actual)
+            }
+            Checker::CaseInsensitive => {
+                let expected = trimmed_lines(expected).join("\n").to_lowercase();
+                let actual = trimmed_lines(actual).join("\n").to_lowercase();
+                CheckResult::new(expected == actual)
+            }
+            Checker::External(cmd) => run_external(cmd, input, expected, actual)?,
+        };
+        Ok(result)
+    }
+}
+
+// Distinguishes temp files of checks running at the same time
+static CHECK_ID: AtomicUsize = AtomicUsize::new(0);
+
+fn run_external(cmd: &str, input: &str, expected: &str, actual: &str) -> Result<CheckResult> {
+    let id = CHECK_ID.fetch_add(1, Ordering::Relaxed);
+    let mut base = std::env::temp_dir();
+    base.push(format!("cargo-boj-{}-check-{}", std::process::id(), id));
+    let input_file = base.with_extension("in");
+    let output_file = base.with_extension("out");
+    let answer_file = base.with_extension("ans");
+    fs::write(&input_file, input)?;
+    fs::write(&output_file, actual)?;
+    fs::write(&answer_file, expected)?;
+    let command_line = format!(
+        "{} \"{}\" \"{}\" \"{}\"",
+        cmd,
+        input_file.display(),
+        output_file.display(),
+        answer_file.display()
+    );
+    let result = shell_command(&command_line).output();
+    for file in [&input_file, &output_file, &answer_file] {
+        let _ = fs::remove_file(file);
+    }
+    let result = result?;
+    // testlib checkers report their verdict on stderr
+    let message = [&result.stderr, &result.stdout]
+        .iter()
+        .map(|buf| String::from_utf8_lossy(buf).trim().to_string())
+        .find(|msg| !msg.is_empty());
+    // testlib exit codes: 0 = OK, 1 = WA, 2 = PE, anything else is a checker failure
+    match result.status.code() {
+        Some(0) => Ok(CheckResult {
+            passed: true,
+            message,
+        }),
+        Some(1 | 2) => Ok(CheckResult {
+            passed: false,
+            message,
+        }),
+        _ => Err(format!(
+            "Error: checker `{}` failed with {}: {}",
+            cmd,
+            result.status,
+            message.unwrap_or_default()
+        ))?,
+    }
+}
@@ -0,0 +1,359 @@
+use std::fmt;
+use std::str::FromStr;
+
+use console::{pad_str, truncate_str, Alignment, Style, Term};
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, DiffTag, TextDiff};
+
+use crate::test::Output;
+
+// How the output of a solution is compared to the expected output
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Compare {
+    // Byte-for-byte comparison
+    Exact,
+    // Ignores trailing whitespace on each line and at the end of the output
+    LineTrim,
+    // Compares whitespace-separated tokens, ignoring how they are laid out in lines
+    Token,
+    // Compares tokens as numbers within the given absolute or relative error
+    Float(f64),
+}
+
+impl FromStr for Compare {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "exact" => Ok(Self::Exact),
+            None if s == "line-trim" => Ok(Self::LineTrim),
+            None if s == "token" => Ok(Self::Token),
+            Some(("float", eps)) => eps
+                .parse()
+                .map(Self::Float)
+                .map_err(|_| format!("invalid epsilon `{}`", eps)),
+            _ => Err("expected `exact`, `line-trim`, `token` or `float:<EPS>`".to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Compare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Compare::Exact => "exact".fmt(f),
+            Compare::LineTrim => "line-trim".fmt(f),
+            Compare::Token => "token".fmt(f),
+            Compare::Float(eps) => write!(f, "float:{}", eps),
+        }
+    }
+}
+
+// How the difference between the output and the expected output is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffStyle {
+    // Changed lines marked with - and +
+    Unified,
+    // Like unified, also highlighting the changed words within the lines
+    Inline,
+    // The output and the expected output in two columns
+    SideBySide,
+    // Only the position of the first mismatching token
+    FirstMismatch,
+}
+
+impl FromStr for DiffStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unified" => Ok(Self::Unified),
+            "inline" => Ok(Self::Inline),
+            "side-by-side" => Ok(Self::SideBySide),
+            "first" => Ok(Self::FirstMismatch),
+            _ => Err("expected `unified`, `inline`, `side-by-side` or `first`".to_string()),
+        }
+    }
+}
+
+// Narrowest column the side-by-side view shrinks to on small terminals
+const MIN_COLUMN_WIDTH: usize = 20;
+
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+    column: usize,
+}
+
+// Whitespace-separated tokens of a line, with their 1-based line and column
+fn line_tokens(line: &str, number: usize) -> Vec<Token<'_>> {
+    line.split_whitespace()
+        .map(|text| {
+            let offset = text.as_ptr() as usize - line.as_ptr() as usize;
+            Token {
+                text,
+                line: number,
+                column: line[..offset].chars().count() + 1,
+            }
+        })
+        .collect()
+}
+
+fn tokens(text: &str) -> Vec<Token<'_>> {
+    text.lines()
+        .enumerate()
+        .flat_map(|(i, line)| line_tokens(line, i + 1))
+        .collect()
+}
+
+fn describe(text: Option<&str>, end: &str) -> String {
+    match text {
+        Some("") => "an empty line".to_string(),
+        Some(text) => format!("`{}`", text),
+        None => end.to_string(),
+    }
+}
+
+pub fn float_eq(actual: f64, expected: f64, abs: f64, rel: f64) -> bool {
+    let diff = (actual - expected).abs();
+    diff <= abs || diff <= rel * expected.abs()
+}
+
+fn token_eq(actual: &str, expected: &str, eps: f64) -> bool {
+    match (actual.parse::<f64>(), expected.parse::<f64>()) {
+        (Ok(a), Ok(e)) => float_eq(a, e, eps, eps),
+        _ => actual == expected,
+    }
+}
+
+impl Compare {
+    // Brings the output into the form that is compared and shown in the diff.
+    // Token-based modes put each token on its own line.
+    pub fn normalize(&self, output: &str) -> String {
+        match self {
+            Compare::Exact => output.to_string(),
+            Compare::LineTrim => output
+                .trim_end()
+                .lines()
+                .map(|l| l.trim_end())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Compare::Token | Compare::Float(_) => {
+                output.split_whitespace().collect::<Vec<_>>().join("\n")
+            }
+        }
+    }
+
+    pub fn matches(&self, actual: &str, expected: &str) -> bool {
+        match self {
+            Compare::Float(eps) => {
+                let actual = actual.split_whitespace().collect::<Vec<_>>();
+                let expected = expected.split_whitespace().collect::<Vec<_>>();
+                actual.len() == expected.len()
+                    && actual.iter().zip(&expected).all(|(a, e)| token_eq(a, e, *eps))
+            }
+            _ => self.normalize(actual) == self.normalize(expected),
+        }
+    }
+
+    fn token_matches(&self, actual: &str, expected: &str) -> bool {
+        match self {
+            Compare::Float(eps) => token_eq(actual, expected, *eps),
+            _ => actual == expected,
+        }
+    }
+
+    // Describes the first place where the output differs from the expected output, if any.
+    fn first_mismatch(&self, actual: &str, expected: &str) -> Option<String> {
+        if let Compare::Token | Compare::Float(_) = self {
+            let actual = tokens(actual);
+            let expected = tokens(expected);
+            let i = (0..actual.len().max(expected.len())).find(|&i| {
+                match (actual.get(i), expected.get(i)) {
+                    (Some(a), Some(e)) => !self.token_matches(a.text, e.text),
+                    _ => true,
+                }
+            })?;
+            // Past the end of the output, point at where the expected token is
+            let at = actual.get(i).or(expected.get(i)).unwrap();
+            return Some(format!(
+                "token {} (line {}, column {}): expected {}, found {}",
+                i + 1,
+                at.line,
+                at.column,
+                describe(expected.get(i).map(|t| t.text), "end of output"),
+                describe(actual.get(i).map(|t| t.text), "end of output")
+            ));
+        }
+        let actual = self.normalize(actual);
+        let expected = self.normalize(expected);
+        let actual = actual.split('\n').collect::<Vec<_>>();
+        let expected = expected.split('\n').collect::<Vec<_>>();
+        let i = (0..actual.len().max(expected.len())).find(|&i| actual.get(i) != expected.get(i))?;
+        let (Some(a), Some(e)) = (actual.get(i), expected.get(i)) else {
+            return Some(format!(
+                "line {}: expected {}, found {}",
+                i + 1,
+                describe(expected.get(i).copied(), "end of output"),
+                describe(actual.get(i).copied(), "end of output")
+            ));
+        };
+        let a_tokens = line_tokens(a, i + 1);
+        let e_tokens = line_tokens(e, i + 1);
+        let j = (0..a_tokens.len().max(e_tokens.len())).find(|&j| {
+            a_tokens.get(j).map(|t| t.text) != e_tokens.get(j).map(|t| t.text)
+        });
+        Some(match j {
+            Some(j) => {
+                let at = a_tokens.get(j).or(e_tokens.get(j)).unwrap();
+                format!(
+                    "line {}, column {}: expected {}, found {}",
+                    at.line,
+                    at.column,
+                    describe(e_tokens.get(j).map(|t| t.text), "end of line"),
+                    describe(a_tokens.get(j).map(|t| t.text), "end of line")
+                )
+            }
+            // The tokens are the same, so the lines differ only in whitespace
+            None => {
+                let column = a.chars().zip(e.chars()).take_while(|(a, e)| a == e).count() + 1;
+                format!("line {}, column {}: whitespace differs", i + 1, column)
+            }
+        })
+    }
+
+    // Pairs up the lines of the normalized outputs as (actual, expected, whether they differ).
+    fn rows<'a>(
+        &self,
+        actual: &'a str,
+        expected: &'a str,
+    ) -> Vec<(Option<&'a str>, Option<&'a str>, bool)> {
+        if let Compare::Float(eps) = self {
+            let actual = actual.lines().collect::<Vec<_>>();
+            let expected = expected.lines().collect::<Vec<_>>();
+            if actual.len() == expected.len() {
+                return actual
+                    .into_iter()
+                    .zip(expected)
+                    .map(|(a, e)| (Some(a), Some(e), !token_eq(a, e, *eps)))
+                    .collect();
+            }
+        }
+        let diff = TextDiff::from_lines(actual, expected);
+        let mut rows = vec![];
+        for op in diff.ops() {
+            let (tag, old, new) = op.as_tag_tuple();
+            let old = &diff.old_slices()[old];
+            let new = &diff.new_slices()[new];
+            for i in 0..old.len().max(new.len()) {
+                let trim = |line: &&'a str| line.trim_end_matches(['\r', '\n']);
+                rows.push((old.get(i).map(trim), new.get(i).map(trim), tag != DiffTag::Equal));
+            }
+        }
+        rows
+    }
+
+    // Prints a diff from the actual output to the expected output, colored if `failed`.
+    pub fn print_diff(
+        &self,
+        out: &mut Output,
+        actual: &str,
+        expected: &str,
+        failed: bool,
+        diff_style: DiffStyle,
+    ) {
+        let styles = if failed {
+            (Style::new().red(), Style::new().green(), Style::new())
+        } else {
+            (Style::new(), Style::new(), Style::new())
+        };
+        if diff_style == DiffStyle::FirstMismatch {
+            if let Some(mismatch) = self.first_mismatch(actual, expected) {
+                out.println(styles.0.apply_to(format!("First mismatch at {}", mismatch)));
+            }
+            return;
+        }
+        let actual = self.normalize(actual);
+        let expected = self.normalize(expected);
+        if diff_style == DiffStyle::SideBySide {
+            let width = (Term::stdout().size().1 as usize)
+                .saturating_sub(3)
+                .max(2 * MIN_COLUMN_WIDTH)
+                / 2;
+            out.println(format_args!(
+                "{}   {}",
+                pad_str("Output", width, Alignment::Left, None),
+                "Expected"
+            ));
+            for (a, e, changed) in self.rows(&actual, &expected) {
+                // The markers follow sdiff
+                let marker = match (a, e) {
+                    (Some(_), None) => '<',
+                    (None, Some(_)) => '>',
+                    _ if changed => '|',
+                    _ => ' ',
+                };
+                let left = pad_str(a.unwrap_or(""), width, Alignment::Left, Some("…"));
+                let right = truncate_str(e.unwrap_or(""), width, "…");
+                if changed {
+                    out.println(format_args!(
+                        "{} {} {}",
+                        styles.0.apply_to(left),
+                        marker,
+                        styles.1.apply_to(right)
+                    ));
+                } else {
+                    out.println(format_args!("{} {} {}", left, marker, right));
+                }
+            }
+            return;
+        }
+        if let Compare::Float(eps) = self {
+            // Tokens within the error are shown as equal, which a text diff can't do
+            let actual = actual.lines().collect::<Vec<_>>();
+            let expected = expected.lines().collect::<Vec<_>>();
+            if actual.len() == expected.len() {
+                for (a, e) in actual.iter().zip(&expected) {
+                    if token_eq(a, e, *eps) {
+                        out.println(format_args!(" {}", styles.2.apply_to(a)));
+                    } else {
+                        out.println(styles.0.apply_to(format!("-{}", a)));
+                        out.println(styles.1.apply_to(format!("+{}", e)));
+                    }
+                }
+                return;
+            }
+        }
+        let diff = TextDiff::from_lines(&actual, &expected);
+        for op in diff.ops() {
+            if diff_style == DiffStyle::Inline {
+                for change in diff.iter_inline_changes(op) {
+                    let (sign, style) = match change.tag() {
+                        ChangeTag::Delete => ("-", &styles.0),
+                        ChangeTag::Insert => ("+", &styles.1),
+                        ChangeTag::Equal => (" ", &styles.2),
+                    };
+                    out.print(style.apply_to(sign));
+                    for (emphasized, value) in change.iter_strings_lossy() {
+                        if emphasized {
+                            out.print(style.clone().underlined().bold().apply_to(value));
+                        } else {
+                            out.print(style.apply_to(value));
+                        }
+                    }
+                    if change.missing_newline() {
+                        out.println("");
+                    }
+                }
+                continue;
+            }
+            for change in diff.iter_changes(op) {
+                let (sign, style) = match change.tag() {
+                    ChangeTag::Delete => ("-", &styles.0),
+                    ChangeTag::Insert => ("+", &styles.1),
+                    ChangeTag::Equal => (" ", &styles.2),
+                };
+                out.print(format_args!("{}{}", style.apply_to(sign), style.apply_to(change)));
+            }
+        }
+    }
+}
@@ -1,11 +1,15 @@
+use chrono::{DateTime, Utc};
 use directories::ProjectDirs;
 use once_cell::sync::Lazy;
 use reqwest::blocking::get;
 use scraper::{Html, Selector};
 use serde::{Deserialize, Serialize};
+
+use crate::compare::Compare;
 use std::fs;
 use std::path::PathBuf;
 use std::io::Write;
+use std::time::Duration;
 
 static DIR: Lazy<ProjectDirs> =
     Lazy::new(|| ProjectDirs::from("com.github", "bubbler-4", "cargo-boj").unwrap());
@@ -43,6 +47,57 @@ static LANGUAGE_LIST_FILE: Lazy<PathBuf> = Lazy::new(|| {
     file
 });
 
+// create language profiles file from the built-in profiles on first access, so that it can be edited
+static LANGUAGE_PROFILES_FILE: Lazy<PathBuf> = Lazy::new(|| {
+    let profiles_str = include_str!("../assets/languageProfiles.json");
+
+    let dir = DIR.config_dir().to_path_buf();
+    fs::create_dir_all(dir.clone()).unwrap();
+    let mut file = dir;
+    file.push("languageProfiles.json");
+    let file_handle = fs::OpenOptions::new()
+        .append(true)
+        .create_new(true)
+        .open(file.clone());
+    if let Ok(mut handle) = file_handle {
+        write!(handle, "{}", profiles_str).unwrap();
+    }
+    file
+});
+
+// create settings file with the defaults on first access, so that it can be found and edited
+static SETTINGS_FILE: Lazy<PathBuf> = Lazy::new(|| {
+    let dir = DIR.config_dir().to_path_buf();
+    fs::create_dir_all(dir.clone()).unwrap();
+    let mut file = dir;
+    file.push("settings.json");
+    let file_handle = fs::OpenOptions::new()
+        .append(true)
+        .create_new(true)
+        .open(file.clone());
+    if let Ok(mut handle) = file_handle {
+        let settings_str = serde_json::to_string_pretty(&Settings::default()).unwrap();
+        writeln!(handle, "{}", settings_str).unwrap();
+    }
+    file
+});
+
+// create history file with no submissions on first access
+static HISTORY_FILE: Lazy<PathBuf> = Lazy::new(|| {
+    let dir = DIR.data_dir().to_path_buf();
+    fs::create_dir_all(dir.clone()).unwrap();
+    let mut file = dir;
+    file.push("history.json");
+    let file_handle = fs::OpenOptions::new()
+        .append(true)
+        .create_new(true)
+        .open(file.clone());
+    if let Ok(mut handle) = file_handle {
+        writeln!(handle, "[]").unwrap();
+    }
+    file
+});
+
 static CACHE_DIR: Lazy<PathBuf> = Lazy::new(|| {
     let dir = DIR.cache_dir().to_path_buf();
     fs::create_dir_all(dir.clone()).unwrap();
@@ -70,7 +125,23 @@ fn cache_file(problem_id: &str) -> PathBuf {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProblemData {
     pub spj: bool,
+    pub two_steps: bool,
+    pub time_limit: Option<Duration>,
+    // In kilobytes
+    pub memory_limit: Option<u64>,
     pub testcases: Vec<(String, String)>,
+    // Set by the user rather than fetched, so it is kept across refreshes
+    #[serde(default)]
+    pub compare: Option<Compare>,
+}
+
+// Parses the number at the start of a problem info cell, e.g. "0.5 초 (추가 시간 없음)" -> 0.5
+fn leading_number(s: &str) -> Option<f64> {
+    let s = s.trim();
+    let end = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    s[..end].parse().ok()
 }
 
 impl ProblemData {
@@ -87,11 +158,26 @@ impl ProblemData {
             Selector::parse("span.problem-label-spj, span.problem-label-partial").unwrap();
         let mut it = html.select(&spj_selector);
         let spj = it.next().is_some();
-        // For Interactive and Two Step problems, do not run the test cases at all
-        let dont_run_selector = Selector::parse("span.problem-label-two-steps, span.problem-label-interactive").unwrap();
+        // Two Step problems run the solution twice, feeding the first output to the second run
+        let two_steps_selector = Selector::parse("span.problem-label-two-steps").unwrap();
+        let two_steps = html.select(&two_steps_selector).next().is_some();
+        // For Interactive problems, do not run the test cases at all
+        let dont_run_selector = Selector::parse("span.problem-label-interactive").unwrap();
         let mut it = html.select(&dont_run_selector);
         let dont_run = it.next().is_some();
-        
+        // The first two columns of the problem info table are the time limit in seconds
+        // and the memory limit in MB
+        let info_selector = Selector::parse("#problem-info tbody td").unwrap();
+        let mut info = html.select(&info_selector).map(|el| el.text().collect::<String>());
+        let time_limit = info
+            .next()
+            .and_then(|s| leading_number(&s))
+            .map(Duration::from_secs_f64);
+        let memory_limit = info
+            .next()
+            .and_then(|s| leading_number(&s))
+            .map(|mb| (mb * 1024.0) as u64);
+
         let selector = Selector::parse("pre.sampledata").unwrap();
         let mut it = html.select(&selector);
         let mut testcases = vec![];
@@ -102,19 +188,102 @@ impl ProblemData {
                 testcases.push((input, output));
             }
         }
-        Self { spj, testcases }
+        Self {
+            spj,
+            two_steps,
+            time_limit,
+            memory_limit,
+            testcases,
+            compare: None,
+        }
     }
 
     pub fn load(problem_id: &str, refresh: bool) -> Self {
         let cache_path = cache_file(problem_id);
         let cache_str = fs::read_to_string(cache_path.as_path()).unwrap();
-        serde_json::from_str(&cache_str).ok().filter(|_| !refresh).unwrap_or_else(|| {
-            let data = Self::fetch_test_cases(problem_id);
-            let cache_str = serde_json::to_string(&data).unwrap();
-            fs::write(cache_path.as_path(), cache_str).unwrap();
+        let cached = serde_json::from_str::<Self>(&cache_str).ok();
+        let compare = cached.as_ref().and_then(|data| data.compare);
+        cached.filter(|_| !refresh).unwrap_or_else(|| {
+            let data = Self {
+                compare,
+                ..Self::fetch_test_cases(problem_id)
+            };
+            data.save(problem_id);
             data
         })
     }
+
+    pub fn save(&self, problem_id: &str) {
+        let cache_str = serde_json::to_string(self).unwrap();
+        fs::write(cache_file(problem_id), cache_str).unwrap();
+    }
+}
+
+// Custom test cases live in `tests/<problem id>/<name>.in` and `<name>.out`,
+// relative to the current directory (usually the crate root).
+pub fn custom_case_dir(problem_id: &str) -> PathBuf {
+    let mut dir = "tests".parse::<PathBuf>().unwrap();
+    dir.push(problem_id);
+    dir
+}
+
+#[derive(Debug, Clone)]
+pub struct CustomCase {
+    pub name: String,
+    pub input: String,
+    pub output: String,
+}
+
+impl CustomCase {
+    // Loads all complete (having both .in and .out) custom cases for the problem, sorted by name.
+    pub fn load_all(problem_id: &str) -> Vec<Self> {
+        let Ok(entries) = fs::read_dir(custom_case_dir(problem_id)) else {
+            return vec![];
+        };
+        let mut cases = entries
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "in" {
+                    return None;
+                }
+                let name = path.file_stem()?.to_str()?.to_owned();
+                let input = fs::read_to_string(&path).ok()?;
+                let output = fs::read_to_string(path.with_extension("out")).ok()?;
+                Some(Self {
+                    name,
+                    input,
+                    output,
+                })
+            })
+            .collect::<Vec<_>>();
+        // Sort numeric names numerically, so that 10 comes after 9
+        cases.sort_by(|a, b| {
+            let key = |name: &str| (name.parse::<u64>().unwrap_or(u64::MAX), name.to_owned());
+            key(&a.name).cmp(&key(&b.name))
+        });
+        cases
+    }
+
+    // Picks the smallest positive integer not used as a case name yet.
+    pub fn next_name(problem_id: &str) -> String {
+        let names = Self::load_all(problem_id)
+            .into_iter()
+            .map(|case| case.name)
+            .collect::<Vec<_>>();
+        (1..)
+            .map(|n: u64| n.to_string())
+            .find(|name| !names.contains(name))
+            .unwrap()
+    }
+
+    pub fn save(&self, problem_id: &str) -> std::io::Result<PathBuf> {
+        let mut path = custom_case_dir(problem_id);
+        fs::create_dir_all(&path)?;
+        path.push(format!("{}.in", self.name));
+        fs::write(&path, &self.input)?;
+        fs::write(path.with_extension("out"), &self.output)?;
+        Ok(path)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -169,3 +338,114 @@ impl LanguageTypes {
         }
     }
 }
+
+// How to build and run a solution in a language other than Rust.
+// The commands run through the shell; `compile` runs in the build directory, which holds the source as
+// `Main.<first extension>` as on BOJ, and `{dir}` is replaced with the quoted path of that directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LanguageProfile {
+    // The language ID on BOJ
+    pub id: usize,
+    pub name: String,
+    // Source file extensions, without the dot. The first profile listing an extension is used for it.
+    pub extensions: Vec<String>,
+    #[serde(default)]
+    pub compile: Option<String>,
+    pub run: String,
+}
+
+pub struct LanguageProfiles;
+
+impl LanguageProfiles {
+    pub fn load() -> crate::Result<Vec<LanguageProfile>> {
+        let profiles_str = fs::read_to_string(LANGUAGE_PROFILES_FILE.as_path())?;
+        match serde_json::from_str(&profiles_str) {
+            Ok(profiles) => Ok(profiles),
+            Err(e) => Err(format!(
+                "Error: invalid language profiles file {}: {}",
+                LANGUAGE_PROFILES_FILE.display(),
+                e
+            ))?,
+        }
+    }
+
+    pub fn file() -> PathBuf {
+        LANGUAGE_PROFILES_FILE.clone()
+    }
+}
+
+// User preferences, edited by hand in settings.json in the config directory.
+// Missing fields take their default values.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // Run all test cases even after one fails, and show a summary
+    pub keep_going: bool,
+    // Test the source before submitting it, and only submit if the tests pass
+    pub test_first: bool,
+}
+
+impl Settings {
+    pub fn load() -> crate::Result<Self> {
+        let settings_str = fs::read_to_string(SETTINGS_FILE.as_path())?;
+        match serde_json::from_str(&settings_str) {
+            Ok(settings) => Ok(settings),
+            Err(e) => Err(format!(
+                "Error: invalid settings file {}: {}",
+                SETTINGS_FILE.display(),
+                e
+            ))?,
+        }
+    }
+}
+
+// A solution submitted with `cargo boj submit`, as recorded in history.json in the data directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Submission {
+    pub problem_id: String,
+    pub solution_id: String,
+    pub language: usize,
+    // As sent to BOJ: open, close or onlyaccepted, or the account's default if --code-open was not given
+    pub code_open: String,
+    // SHA-256 of the submitted source, in hex
+    pub source_hash: String,
+    pub timestamp: DateTime<Utc>,
+    // None until the judging finishes while `cargo boj submit` waits for it
+    pub verdict: Option<String>,
+    // In milliseconds
+    pub time: Option<u64>,
+    // In kilobytes
+    pub memory: Option<u64>,
+}
+
+pub struct History;
+
+impl History {
+    // Submissions in the order they were made
+    pub fn load() -> crate::Result<Vec<Submission>> {
+        let history_str = fs::read_to_string(HISTORY_FILE.as_path())?;
+        match serde_json::from_str(&history_str) {
+            Ok(history) => Ok(history),
+            Err(e) => Err(format!(
+                "Error: invalid history file {}: {}",
+                HISTORY_FILE.display(),
+                e
+            ))?,
+        }
+    }
+
+    // Adds the submission, or replaces the one with the same solution ID
+    pub fn save(submission: &Submission) -> crate::Result<()> {
+        let mut history = Self::load()?;
+        match history
+            .iter_mut()
+            .find(|s| s.solution_id == submission.solution_id)
+        {
+            Some(s) => *s = submission.clone(),
+            None => history.push(submission.clone()),
+        }
+        let history_str = serde_json::to_string_pretty(&history)?;
+        fs::write(HISTORY_FILE.as_path(), history_str)?;
+        Ok(())
+    }
+}
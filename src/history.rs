@@ -0,0 +1,130 @@
+use chrono::Local;
+use console::Style;
+
+use crate::datastore::{self, Submission};
+use crate::optparse::{get_language_id, get_language_name, History};
+use crate::Result;
+
+// Short forms of BOJ's verdicts, which can be used to filter the history
+fn abbreviation(verdict: &str) -> &str {
+    match verdict {
+        "Accepted" => "AC",
+        "Partially Accepted" => "PAC",
+        "Presentation Error" => "PE",
+        "Wrong Answer" => "WA",
+        "Time Limit Exceeded" => "TLE",
+        "Memory Limit Exceeded" => "MLE",
+        "Output Limit Exceeded" => "OLE",
+        "Runtime Error" => "RTE",
+        "Compilation Error" => "CE",
+        _ => verdict,
+    }
+}
+
+fn verdict_style(verdict: Option<&str>) -> Style {
+    match verdict {
+        None => Style::new().dim(),
+        Some("Accepted") => Style::new().green(),
+        Some("Partially Accepted") => Style::new().yellow(),
+        Some(_) => Style::new().red(),
+    }
+}
+
+fn matches(submission: &Submission, opts: &History, language: Option<usize>) -> bool {
+    let problem_matches = opts
+        .problem_id
+        .as_ref()
+        .is_none_or(|problem_id| &submission.problem_id == problem_id);
+    let verdict_matches = opts.verdict.as_ref().is_none_or(|filter| {
+        submission.verdict.as_deref().is_some_and(|verdict| {
+            verdict.eq_ignore_ascii_case(filter) || abbreviation(verdict).eq_ignore_ascii_case(filter)
+        })
+    });
+    let language_matches = language.is_none_or(|language| submission.language == language);
+    problem_matches && verdict_matches && language_matches
+}
+
+// Lists the recorded submissions matching the filters, latest first.
+pub fn history(opts: History) -> Result<()> {
+    let language = opts
+        .language
+        .clone()
+        .map(|language| get_language_id(Some(language)));
+    let submissions = datastore::History::load()?;
+    let submissions = submissions
+        .iter()
+        .rev()
+        .filter(|submission| matches(submission, &opts, language))
+        .take(opts.limit.unwrap_or(usize::MAX))
+        .collect::<Vec<_>>();
+    if submissions.is_empty() {
+        println!("No submissions found.");
+        return Ok(());
+    }
+    let rows = submissions
+        .iter()
+        .map(|submission| {
+            let language = get_language_name(submission.language)
+                .unwrap_or_else(|| submission.language.to_string());
+            let time = submission
+                .time
+                .map_or("-".to_string(), |time| format!("{} ms", time));
+            let memory = submission
+                .memory
+                .map_or("-".to_string(), |memory| format!("{} KB", memory));
+            [
+                submission
+                    .timestamp
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string(),
+                submission.problem_id.clone(),
+                submission.solution_id.clone(),
+                language,
+                submission.verdict.clone().unwrap_or("-".to_string()),
+                time,
+                memory,
+                submission.code_open.clone(),
+                submission.source_hash.chars().take(8).collect(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let header = [
+        "Submitted", "Problem", "Solution", "Language", "Verdict", "Time", "Memory", "Open",
+        "Source",
+    ];
+    let widths = header
+        .iter()
+        .enumerate()
+        .map(|(i, title)| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain([title.len()])
+                .max()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+    let line = |cells: &[String]| {
+        cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, &width)| format!("{:<width$}", cell))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    let header = header.map(String::from);
+    println!("{}", Style::new().bold().apply_to(line(&header)));
+    for (submission, row) in submissions.iter().zip(&rows) {
+        // Padded before styling, as the escape codes would count towards the width
+        let mut cells = row.clone();
+        cells[4] = format!("{:<width$}", cells[4], width = widths[4]);
+        let styled = verdict_style(submission.verdict.as_deref())
+            .apply_to(&cells[4])
+            .to_string();
+        cells[4] = styled;
+        println!("{}", line(&cells));
+    }
+    Ok(())
+}
@@ -0,0 +1,255 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use console::Style;
+
+use crate::test::{
+    describe_status, format_elapsed, format_usage, join_within, kill_program, print_stderr,
+    reaped, shell_command, spawn_group, spawn_program, spawn_reader, wait_with_memory, CpuTime,
+    Finished, Limits, Outcome, Output, PeakMemory, Program, Verdict, IO_GRACE, POLL_INTERVAL_MAX,
+    POLL_INTERVAL_MIN,
+};
+use crate::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    ToInteractor,
+    ToSolution,
+}
+
+type Transcript = Arc<Mutex<Vec<(Direction, String)>>>;
+
+const TRANSCRIPT_MAX_LINES: usize = 50;
+// The interactor gets this much time after the solution finishes to give its verdict
+const INTERACTOR_GRACE: Duration = Duration::from_secs(1);
+
+// Distinguishes temp files of runs happening at the same time
+static RUN_ID: AtomicUsize = AtomicUsize::new(0);
+
+// Forwards lines from one process to the other as soon as they are written, recording them.
+fn relay(
+    from: impl Read + Send + 'static,
+    mut to: impl Write + Send + 'static,
+    direction: Direction,
+    transcript: Transcript,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut from = BufReader::new(from);
+        let mut line = vec![];
+        loop {
+            line.clear();
+            match from.read_until(b'\n', &mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+            transcript
+                .lock()
+                .unwrap()
+                .push((direction, String::from_utf8_lossy(&line).to_string()));
+            // The other side may have exited already
+            if to.write_all(&line).and_then(|_| to.flush()).is_err() {
+                break;
+            }
+        }
+        // Dropping `to` closes the pipe, so the other side sees EOF
+    })
+}
+
+fn render_transcript(transcript: &[(Direction, String)]) -> String {
+    transcript
+        .iter()
+        .map(|(direction, line)| match direction {
+            Direction::ToInteractor => format!("> {}", line),
+            Direction::ToSolution => format!("< {}", line),
+        })
+        .collect()
+}
+
+fn print_transcript(out: &mut Output, id: usize, transcript: &[(Direction, String)]) -> Result<()> {
+    let to_interactor = Style::new().cyan();
+    let to_solution = Style::new().magenta();
+    out.println(Style::new().yellow().apply_to("Transcript:"));
+    for (direction, line) in transcript.iter().take(TRANSCRIPT_MAX_LINES) {
+        let line = line.trim_end();
+        match direction {
+            Direction::ToInteractor => out.println(to_interactor.apply_to(format!("> {}", line))),
+            Direction::ToSolution => out.println(to_solution.apply_to(format!("< {}", line))),
+        }
+    }
+    if transcript.len() > TRANSCRIPT_MAX_LINES {
+        let mut path = std::env::temp_dir();
+        path.push(format!("cargo-boj-{}-transcript-{}.txt", std::process::id(), id));
+        fs::write(&path, render_transcript(transcript))?;
+        out.println(format_args!(
+            "... ({} more lines, full transcript saved to {})",
+            transcript.len() - TRANSCRIPT_MAX_LINES,
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+// Runs the solution against the interactor with their stdin and stdout cross-connected.
+// The interactor is run as `<interactor> <input file> <output file>` (testlib convention),
+// and its exit code decides the verdict: 0 means accepted.
+// The solution is reaped by `wait_with_memory`, which clippy can't see.
+#[allow(clippy::zombie_processes)]
+pub fn run_interactive(
+    program: &Program,
+    interactor: &str,
+    limits: &Limits,
+    input: &str,
+    out: &mut Output,
+) -> Result<Outcome> {
+    let id = RUN_ID.fetch_add(1, Ordering::Relaxed);
+    let mut base = std::env::temp_dir();
+    base.push(format!("cargo-boj-{}-interactor-{}", std::process::id(), id));
+    let input_file = base.with_extension("in");
+    let output_file = base.with_extension("out");
+    fs::write(&input_file, input)?;
+    let command_line = format!(
+        "{} \"{}\" \"{}\"",
+        interactor,
+        input_file.display(),
+        output_file.display()
+    );
+    let mut interactor_handle = spawn_group(
+        shell_command(&command_line)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped()),
+    )?;
+    let mut solution = spawn_program(program, &[], limits);
+    let now = Instant::now();
+
+    let transcript = Transcript::default();
+    let to_interactor = relay(
+        solution.stdout.take().unwrap(),
+        interactor_handle.stdin.take().unwrap(),
+        Direction::ToInteractor,
+        transcript.clone(),
+    );
+    let to_solution = relay(
+        interactor_handle.stdout.take().unwrap(),
+        solution.stdin.take().unwrap(),
+        Direction::ToSolution,
+        transcript.clone(),
+    );
+    let solution_stderr = spawn_reader(solution.stderr.take().unwrap());
+    let interactor_stderr = spawn_reader(interactor_handle.stderr.take().unwrap());
+
+    let mut solution_result = None;
+    let mut interactor_status = None;
+    let mut timed_out = false;
+    let mut solution_elapsed = Duration::ZERO;
+    let mut peak = PeakMemory::default();
+    let mut cpu_time = CpuTime::default();
+    let mut interval = POLL_INTERVAL_MIN;
+    while solution_result.is_none() || interactor_status.is_none() {
+        if solution_result.is_none() {
+            solution_result = wait_with_memory(&mut solution, &mut peak, false);
+            solution_elapsed = now.elapsed();
+            if solution_result.is_none() && limits.jobs > 1 {
+                cpu_time.sample(solution.id());
+            }
+        }
+        if interactor_status.is_none() {
+            interactor_status = interactor_handle.try_wait()?;
+        }
+        let deadline = match solution_result {
+            None => limits.wall_limit(),
+            Some(_) => Some(solution_elapsed + INTERACTOR_GRACE),
+        };
+        let over_cpu = solution_result.is_none() && limits.cpu_exceeded(&cpu_time);
+        if over_cpu || deadline.is_some_and(|deadline| now.elapsed() > deadline) {
+            if solution_result.is_none() {
+                timed_out = true;
+                kill_program(&mut solution)?;
+                solution_result = wait_with_memory(&mut solution, &mut peak, true);
+                solution_elapsed = now.elapsed();
+            }
+            if interactor_status.is_none() {
+                kill_program(&mut interactor_handle)?;
+                interactor_status = Some(interactor_handle.wait()?);
+            }
+            break;
+        }
+        thread::sleep(interval);
+        interval = (interval * 2).min(POLL_INTERVAL_MAX);
+    }
+    let Finished {
+        status,
+        memory,
+        cpu,
+    } = solution_result.unwrap();
+    let (solution_elapsed, timed_out) = if timed_out {
+        // The CPU time of a killed program misses the processes it started, which were never waited for
+        let exact = matches!(program, Program::Exe { .. }) && !cpu_time.forked;
+        (limits.time_killed(solution_elapsed, cpu.filter(|_| exact)), true)
+    } else {
+        limits.time_used(solution_elapsed, cpu)
+    };
+    let interactor_status = interactor_status.unwrap();
+    reaped(&interactor_handle);
+    let _ = fs::remove_file(&input_file);
+    let _ = fs::remove_file(&output_file);
+
+    // Both processes are gone now, and so are their descendants if they were killed, so the pipes
+    // are closed unless a --cmd shell that exited by itself left some descendant running
+    join_within(to_interactor, IO_GRACE);
+    join_within(to_solution, IO_GRACE);
+    let transcript = transcript.lock().unwrap();
+    print_transcript(out, id, &transcript)?;
+    let stderr = join_within(solution_stderr, IO_GRACE)
+        .map(|buf| String::from_utf8_lossy(&buf).to_string())
+        .unwrap_or_default();
+    let interactor_message = join_within(interactor_stderr, IO_GRACE)
+        .map(|buf| String::from_utf8_lossy(&buf).to_string())
+        .unwrap_or_default();
+    if !interactor_message.trim().is_empty() {
+        out.println(format_args!("Interactor: {}", interactor_message.trim()));
+    }
+
+    let verdict = if timed_out {
+        out.eprintln(format_args!(
+            "{} ({}s) on input:",
+            Style::new().red().apply_to("Time Limit Exceeded"),
+            format_elapsed(solution_elapsed)
+        ));
+        Verdict::TimeLimitExceeded
+    } else if !interactor_status.success() {
+        out.eprintln(format_args!(
+            "{} (interactor exited with {}) on input:",
+            Style::new().red().apply_to("Test failed"),
+            describe_status(interactor_status)
+        ));
+        Verdict::WrongAnswer
+    } else if !status.success() {
+        out.eprintln(format_args!(
+            "{} ({}) on input:",
+            Style::new().red().apply_to("Runtime Error"),
+            describe_status(status)
+        ));
+        Verdict::RuntimeError
+    } else {
+        Verdict::Accepted
+    };
+    if verdict != Verdict::Accepted {
+        out.eprintln(input);
+    }
+    print_stderr(out, &stderr);
+    if verdict == Verdict::Accepted {
+        out.println(format_usage(solution_elapsed, memory));
+    }
+    Ok(Outcome {
+        verdict,
+        elapsed: solution_elapsed,
+        memory,
+        stdout: render_transcript(&transcript),
+    })
+}
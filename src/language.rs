@@ -0,0 +1,72 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use crate::datastore::{LanguageProfile, LanguageProfiles};
+use crate::optparse::{get_language_id, LanguageType};
+use crate::test::{shell_command, Program};
+use crate::Result;
+
+// Finds the profile for --lang if given, or else the first one handling the source file's extension.
+pub fn find_profile(
+    source: &Path,
+    language: Option<LanguageType>,
+) -> Result<Option<LanguageProfile>> {
+    let profiles = LanguageProfiles::load()?;
+    let profile = match language {
+        Some(language) => {
+            let id = get_language_id(Some(language));
+            profiles.into_iter().find(|profile| profile.id == id)
+        }
+        None => {
+            let Some(extension) = source.extension().and_then(|ext| ext.to_str()) else {
+                return Ok(None);
+            };
+            profiles
+                .into_iter()
+                .find(|profile| profile.extensions.iter().any(|ext| ext == extension))
+        }
+    };
+    Ok(profile)
+}
+
+// The build directory of this process, so that runs happening at the same time don't overwrite each other's files.
+// It is removed when cargo-boj exits.
+pub fn build_dir() -> PathBuf {
+    let mut dir = std::env::temp_dir();
+    dir.push(format!("cargo-boj-{}-build", std::process::id()));
+    dir
+}
+
+// Removes the build directory, once the programs built in it are no longer run
+pub fn remove_build_dir() {
+    let _ = fs::remove_dir_all(build_dir());
+}
+
+// Copies the source into a build directory for the language, compiles it there and returns the program running it.
+pub fn build(source: &Path, profile: &LanguageProfile) -> Result<Program> {
+    let mut dir = build_dir();
+    dir.push(profile.id.to_string());
+    fs::create_dir_all(&dir)?;
+    let extension = profile.extensions.first().map(String::as_str).unwrap_or("txt");
+    if let Err(e) = fs::copy(source, dir.join(format!("Main.{}", extension))) {
+        Err(format!("Error: could not read {}: {}", source.display(), e))?
+    }
+    let quoted_dir = format!("\"{}\"", dir.display());
+    if let Some(compile) = &profile.compile {
+        let compile = compile.replace("{dir}", &quoted_dir);
+        let status = shell_command(&compile)
+            .current_dir(&dir)
+            .stdin(Stdio::null())
+            .status()?;
+        if !status.success() {
+            Err(format!(
+                "Error: {} does not compile as {}: `{}` failed.",
+                source.display(),
+                profile.name,
+                compile
+            ))?
+        }
+    }
+    Ok(Program::Cmd(profile.run.replace("{dir}", &quoted_dir)))
+}
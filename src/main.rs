@@ -1,7 +1,21 @@
+mod bojenv;
+mod bundle;
+mod case;
+mod checker;
+mod compare;
 mod datastore;
+mod history;
+mod interactive;
+mod language;
+mod metadata;
+mod minify;
 mod optparse;
+mod report;
+mod shrink;
+mod stress;
 mod submit;
 mod test;
+mod watch;
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 const UA: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/110.0.0.0 Safari/537.36";
@@ -10,27 +24,71 @@ const UA: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (
 // cargo-boj login [--bojautologin=<str> --onlinejudge=<str>]
 //   register BOJ login cookie information.
 //   if not specified in the flag, the cookies are entered through a prompt.
-// cargo-boj test <prob> [--bin=<bin> | --cmd=<cmd>]
+// cargo-boj test <prob> [--bin=<bin> | --cmd=<cmd>] [--time-factor=<factor>] [--memory-rlimit]
 //   fetch sample tests for <prob> and run tests on the binary.
 //   sample tests are cached by problem id.
+//   each test is killed once it exceeds the problem's time limit (times factor).
+//   peak memory is measured on linux and checked against the memory limit.
+//   --checker=<checker> judges the output with a built-in or external checker (also for spj).
+//   --compare=<mode> sets how outputs are compared; it is remembered for the problem.
+//   --diff-style=<style> shows mismatches as a unified, inline or side-by-side diff, or only the first one.
+//   --format=(text|json|junit) prints the results in a machine-readable format instead.
+//   --keep-going runs all cases and shows a summary; settings.json can make it the default.
+//   --watch re-runs the tests whenever the source file (or --path) changes, with a key to submit.
+//   --interactor=<cmd> runs the solution against an interactor for interactive problems.
+//   --two-steps=<mode> runs the solution twice, feeding the first output to the second run.
+//   --jobs=<n> runs test cases in parallel, printing the results in order; the time limit is checked on cpu time then.
+//   --profile=<profile> and --features=<features> are passed to cargo build (release by default).
+//   --debug builds with the dev profile and --checks enables overflow checks; both show backtraces.
+//   --boj-env compiles the source with rustc as BOJ does for --lang, warning on a rustc version mismatch;
+//   the source is bundled as with submit first, unless --no-bundle is given.
+//   --boj-toolchain uses BOJ's rustc version through rustup.
+//   --diagnostics=errors shows only the compiler errors, one line each, when the build fails.
+//   --path=<path> tests a source file: other languages are compiled and run with the language profile
+//   for --lang or the file extension (languageProfiles.json in the config dir), rust files through their bin.
 //   if bin is supplied, uses its bin name. assumes it is a rust binary.
 //   you can use cmd to run a non-rust program instead.
 //   (when using cmd, it is recommended to compile beforehand to get more accurate timings.)
-//   if neither is supplied, assumes --bin=main.
-// cargo-boj submit <prob> [--path=<path>] [--lang-id=<lang>] [--code-open=(y|n|ac)]
+//   if neither is supplied, uses the bin of src/main.rs or src/bin/main.rs in the current package.
+//   bins and their executables are found through cargo metadata and cargo's build output.
+// cargo-boj submit <prob> [--path=<path>] [--lang-id=<lang>] [--code-open=(y|n|ac)] [--no-bundle] [--test-first]
 //   submit the file at <path> as the solution to problem <prob>.
 //   each option defaults to:
 //   path = src/main.rs or src/bin/main.rs
-//   lang-id = 113 (Rust 2021)
+//   lang-id = the language profile's for the file extension, or 113 (Rust 2021)
 //   code-open = follow account default
+//   rust solutions are bundled into a single file first, unless --no-bundle is given.
+//   sources over BOJ's size limit (64 KiB) are not submitted.
+//   --test-first runs `test` on the exact source to submit first, with the spj prompt, and submits only if it passes;
+//   settings.json can make it the default.
+//   the status is shown until the judging finishes, with the progress while judging, and then the score,
+//   memory, time and code length. exits with 0 only on accepted.
+//   each submission and its final verdict are recorded in history.json in the data dir.
+// cargo-boj case add <prob> [--name=<name>] [--input=<file>] [--output=<file>]
+//   add a custom test case to tests/<prob>/, which `test` runs after the samples.
+//   input and output not given as files are entered through $EDITOR.
+// cargo-boj case list <prob>
+//   list custom test cases for <prob>.
+// cargo-boj stress <prob> (--gen=<bin> | --gen-cmd=<cmd>) (--brute=<bin> | --brute-cmd=<cmd>)
+//     [--bin=<bin> | --cmd=<cmd>] [--iterations=<n>] [--seed=<seed>] [--size=<size>] [--no-shrink]
+//   run the solution and the reference solution on generated inputs and compare the outputs.
+//   the generator gets the seed (and size) as its arguments.
+//   the first failing input is shrunk and saved as a custom case.
+// cargo-boj bundle [--path=<path>] [--output=<file>]
+//   bundle a rust solution into a single file, as submitted: `mod foo;` is replaced with the file's contents,
+//   and the local library crates it uses are appended as modules. tests and unused library modules are removed.
+//   library items unreachable from the solution and library comments are removed, and the size is checked.
+// cargo-boj history [<prob>] [--verdict=<verdict>] [--lang=<lang>] [--limit=<n>]
+//   list the recorded submissions, latest first, filtered by problem, verdict (e.g. AC or WA) and language.
 
 use optparse::*;
-use std::fs;
 use std::io::{self, Write};
 use std::process::ExitCode;
 
 fn main() -> ExitCode {
-    if let Err(s) = inner_main() {
+    let result = inner_main();
+    language::remove_build_dir();
+    if let Err(s) = result {
         eprintln!("{}", s);
         ExitCode::FAILURE
     } else {
@@ -67,49 +125,42 @@ fn inner_main() -> Result<()> {
             credentials.update_cookie(&cookies);
             println!("Cookies set.");
         }
-        Opts::Test(Test {
-            problem_id,
-            bin_or_cmd,
-            spj_prompt,
-            refresh,
-        }) => {
-            // let bin_or_cmd = bin_or_cmd.unwrap_or(BinOrCmd::Bin("main".to_string()));
-            test::test(&problem_id, bin_or_cmd, spj_prompt, refresh)?;
+        Opts::Test(opts) => {
+            test::test(opts)?;
+        }
+        Opts::Submit(opts) => {
+            submit::submit(opts)?;
         }
-        Opts::Submit(Submit {
+        Opts::Case(Case::Add(CaseAdd {
             problem_id,
-            path,
-            language,
-            code_open,
-        }) => {
-            let language = optparse::get_language_id(language);
-            let credentials = datastore::Credentials::load();
-            let Some(cookies) = &credentials.cookies else {
-                println!("Use `cargo-boj login` first to log in.");
-                return Ok(());
-            };
-            let source = if let Some(path) = &path {
-                fs::read_to_string(path).ok()
-            } else {
-                ["src/main.rs", "src/bin/main.rs"]
-                    .into_iter()
-                    .find_map(|file| fs::read_to_string(file).ok())
-            };
-            let Some(source) = source else {
-                if let Some(path) = &path {
-                    println!("{} not found.", path);
-                } else {
-                    println!("Neither src/main.rs nor src/bin/main.rs not found. Try running again at the crate root.");
-                }
-                return Ok(());
+            name,
+            input,
+            output,
+        })) => {
+            case::add(&problem_id, name, input, output)?;
+        }
+        Opts::Case(Case::List(CaseList { problem_id })) => {
+            case::list(&problem_id)?;
+        }
+        Opts::Stress(opts) => {
+            stress::stress(opts)?;
+        }
+        Opts::Bundle(Bundle { path, output }) => {
+            let Some(path) = submit::solution_path(path) else {
+                Err("Error: neither src/main.rs nor src/bin/main.rs found. Please specify --path flag.")?
             };
-            submit::submit_solution(
-                cookies,
-                &problem_id,
-                &source,
-                language,
-                code_open.map(|x| x.to_string()),
-            );
+            let bundled = minify::minify(&bundle::bundle(&path)?)?;
+            match submit::check_size(&bundled) {
+                Ok(size) => eprintln!("Size: {} bytes (BOJ's limit is {} bytes)", size, submit::SOURCE_LIMIT),
+                Err(e) => eprintln!("{}", e),
+            }
+            match output {
+                Some(output) => std::fs::write(output, bundled)?,
+                None => print!("{}", bundled),
+            }
+        }
+        Opts::History(opts) => {
+            history::history(opts)?;
         }
     }
     Ok(())
@@ -0,0 +1,164 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use serde::Deserialize;
+
+use crate::Result;
+
+// The parts of `cargo metadata` output that are used
+#[derive(Deserialize)]
+struct Metadata {
+    packages: Vec<Package>,
+}
+
+#[derive(Deserialize)]
+struct Package {
+    manifest_path: PathBuf,
+    targets: Vec<Target>,
+    dependencies: Vec<Dependency>,
+}
+
+#[derive(Deserialize)]
+struct Dependency {
+    rename: Option<String>,
+    // Set for path dependencies
+    path: Option<PathBuf>,
+    // None for normal dependencies
+    kind: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Target {
+    name: String,
+    kind: Vec<String>,
+    src_path: PathBuf,
+}
+
+pub struct Bin {
+    pub name: String,
+    pub src_path: PathBuf,
+}
+
+// A library crate on the local filesystem, which can be bundled into a solution
+pub struct LocalLib {
+    // The name the crate is used by in code
+    pub name: String,
+    pub src_path: PathBuf,
+}
+
+// The package containing the current directory, which is the innermost one in a workspace
+pub struct CurrentPackage {
+    pub dir: PathBuf,
+    pub bins: Vec<Bin>,
+    lib: Option<LocalLib>,
+    // The directories of path dependencies, with the names they are renamed to
+    path_dependencies: Vec<(PathBuf, Option<String>)>,
+}
+
+fn metadata(manifest_path: Option<&Path>) -> Result<Metadata> {
+    let mut command = Command::new("cargo");
+    command.args(["metadata", "--no-deps", "--format-version", "1"]);
+    if let Some(manifest_path) = manifest_path {
+        command.arg("--manifest-path").arg(manifest_path);
+    }
+    let output = command.stdin(Stdio::null()).output()?;
+    if !output.status.success() {
+        Err(format!(
+            "Error: `cargo metadata` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))?
+    }
+    Ok(serde_json::from_slice::<Metadata>(&output.stdout)?)
+}
+
+impl CurrentPackage {
+    pub fn load() -> Result<Self> {
+        let metadata = metadata(None)?;
+        // cargo reports canonical paths
+        let current_dir = std::env::current_dir()?.canonicalize()?;
+        let package = metadata
+            .packages
+            .into_iter()
+            .filter(|package| current_dir.starts_with(package.manifest_path.parent().unwrap()))
+            .max_by_key(|package| package.manifest_path.components().count());
+        let Some(package) = package else {
+            Err("Error: the current directory is not inside a package. Please specify --bin flag.")?
+        };
+        Ok(Self::from_package(package))
+    }
+
+    // Loads the package whose root is the given directory
+    fn load_at(dir: &Path) -> Result<Self> {
+        let manifest_path = dir.join("Cargo.toml");
+        let package = metadata(Some(&manifest_path))?
+            .packages
+            .into_iter()
+            .find(|package| package.manifest_path.parent() == Some(dir));
+        match package {
+            Some(package) => Ok(Self::from_package(package)),
+            None => Err(format!("Error: no package found at {}.", dir.display()))?,
+        }
+    }
+
+    fn from_package(package: Package) -> Self {
+        let mut bins = vec![];
+        let mut lib = None;
+        for target in package.targets {
+            if target.kind.iter().any(|kind| kind == "bin") {
+                bins.push(Bin {
+                    name: target.name,
+                    src_path: target.src_path,
+                });
+            } else if target.kind.iter().any(|kind| kind == "lib" || kind == "rlib") {
+                lib = Some(LocalLib {
+                    name: target.name,
+                    src_path: target.src_path,
+                });
+            }
+        }
+        let path_dependencies = package
+            .dependencies
+            .into_iter()
+            .filter(|dependency| dependency.kind.is_none())
+            .filter_map(|dependency| Some((dependency.path?, dependency.rename)))
+            .collect();
+        Self {
+            dir: package.manifest_path.parent().unwrap().to_path_buf(),
+            bins,
+            lib,
+            path_dependencies,
+        }
+    }
+
+    // The package's own library and the libraries of its path dependencies, including indirect ones
+    pub fn local_libs(self) -> Result<Vec<LocalLib>> {
+        let mut libs = vec![];
+        let mut visited = HashSet::from([self.dir.clone()]);
+        let mut queue = vec![(self, None)];
+        while let Some((package, rename)) = queue.pop() {
+            for (dir, rename) in &package.path_dependencies {
+                if visited.insert(dir.clone()) {
+                    queue.push((Self::load_at(dir)?, rename.clone()));
+                }
+            }
+            if let Some(mut lib) = package.lib {
+                if let Some(rename) = rename {
+                    lib.name = rename.replace('-', "_");
+                }
+                libs.push(lib);
+            }
+        }
+        Ok(libs)
+    }
+
+    // Finds the bin built from the given source file, relative to the package root
+    pub fn bin_at(&self, path: impl AsRef<Path>) -> Option<&Bin> {
+        let path = self.dir.join(path);
+        self.bins.iter().find(|bin| bin.src_path == path)
+    }
+
+    pub fn bin_named(&self, name: &str) -> Option<&Bin> {
+        self.bins.iter().find(|bin| bin.name == name)
+    }
+}
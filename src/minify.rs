@@ -0,0 +1,618 @@
+use std::collections::HashSet;
+use std::ops::Range;
+use std::str::FromStr;
+
+use proc_macro2::{TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::spanned::Spanned;
+use syn::{ImplItem, Item};
+
+use crate::bundle::{apply_edits, collect_idents, parse, Bundled, Offsets};
+use crate::Result;
+
+// Shrinks a bundled solution before it is submitted: the items of the bundled libraries that can't be reached
+// from the solution are removed, and then the comments (including doc comments and their doc-tests) in the libraries.
+// The solution's own code is kept as written.
+pub fn minify(bundled: &Bundled) -> Result<String> {
+    let source = remove_unreachable(&bundled.source, &bundled.libs)?;
+    strip_comments(&source, &bundled.libs)
+}
+
+fn tokens_of(node: &impl ToTokens) -> HashSet<String> {
+    let mut idents = HashSet::new();
+    collect_idents(node.to_token_stream(), &mut idents);
+    idents
+}
+
+// The name an item is referred to by, or None if it is always kept
+fn item_names(item: &Item) -> Option<Vec<String>> {
+    let ident = match item {
+        Item::Const(item) if item.ident != "_" => &item.ident,
+        Item::Enum(item) => &item.ident,
+        Item::Fn(item) => &item.sig.ident,
+        Item::Macro(syn::ItemMacro {
+            ident: Some(ident),
+            ..
+        }) => ident,
+        Item::Static(item) => &item.ident,
+        Item::Struct(item) => &item.ident,
+        Item::Trait(item) => {
+            // Trait methods are called without naming the trait
+            let mut names = vec![item.ident.to_string()];
+            for trait_item in &item.items {
+                match trait_item {
+                    syn::TraitItem::Const(item) => names.push(item.ident.to_string()),
+                    syn::TraitItem::Fn(item) => names.push(item.sig.ident.to_string()),
+                    syn::TraitItem::Type(item) => names.push(item.ident.to_string()),
+                    _ => {}
+                }
+            }
+            return Some(names);
+        }
+        Item::TraitAlias(item) => &item.ident,
+        Item::Type(item) => &item.ident,
+        Item::Union(item) => &item.ident,
+        // Imports from other crates may bring traits into scope, which are used without being named
+        Item::Use(item) if is_external(&item.tree) => return None,
+        Item::Use(item) => {
+            let mut names = vec![];
+            return use_names(&item.tree, &mut names).then_some(names);
+        }
+        _ => return None,
+    };
+    Some(vec![ident.to_string()])
+}
+
+fn is_external(tree: &syn::UseTree) -> bool {
+    match tree {
+        syn::UseTree::Path(path) => ["std", "core", "alloc"].iter().any(|name| path.ident == name),
+        syn::UseTree::Group(group) => group.items.iter().any(is_external),
+        _ => false,
+    }
+}
+
+// Collects the names a `use` brings into scope. Returns false for a glob import, which is always kept.
+fn use_names(tree: &syn::UseTree, names: &mut Vec<String>) -> bool {
+    match tree {
+        syn::UseTree::Path(path) => match &*path.tree {
+            // `use foo::{self}` imports `foo`
+            syn::UseTree::Name(name) if name.ident == "self" => {
+                names.push(path.ident.to_string());
+                true
+            }
+            tree => use_names(tree, names),
+        },
+        syn::UseTree::Name(name) => {
+            names.push(name.ident.to_string());
+            true
+        }
+        syn::UseTree::Rename(rename) => {
+            names.push(rename.rename.to_string());
+            true
+        }
+        syn::UseTree::Glob(_) => false,
+        syn::UseTree::Group(group) => group.items.iter().all(|tree| use_names(tree, names)),
+    }
+}
+
+// The last identifier of a type's path, e.g. `SegTree` for `seg::SegTree<T>`
+fn type_name(ty: &syn::Type) -> Option<String> {
+    match ty {
+        syn::Type::Path(ty) => Some(ty.path.segments.last()?.ident.to_string()),
+        syn::Type::Reference(ty) => type_name(&ty.elem),
+        _ => None,
+    }
+}
+
+enum Condition {
+    // Reachable if one of the names is mentioned
+    Named(Vec<String>),
+    // An impl is reachable if its type and trait are, as far as they are defined in the libraries
+    Impl {
+        self_ty: Option<String>,
+        trait_: Option<String>,
+    },
+}
+
+struct Node {
+    condition: Condition,
+    // The inherent impl a method belongs to, which has to be reachable as well
+    parent: Option<usize>,
+    // The identifiers the item mentions, which become reachable with it
+    idents: HashSet<String>,
+    range: Range<usize>,
+}
+
+struct Graph<'a> {
+    offsets: &'a Offsets,
+    nodes: Vec<Node>,
+    // Identifiers of the code that is always kept
+    roots: HashSet<String>,
+}
+
+impl Graph<'_> {
+    fn add_items(&mut self, items: &[Item]) {
+        for item in items {
+            match item {
+                Item::Mod(module) => {
+                    // Modules are kept, and their items are looked at one by one
+                    if let Some((_, items)) = &module.content {
+                        self.add_items(items);
+                    }
+                }
+                Item::Impl(item) => self.add_impl(item),
+                item => match item_names(item) {
+                    Some(names) => self.nodes.push(Node {
+                        condition: Condition::Named(names),
+                        parent: None,
+                        idents: tokens_of(item),
+                        range: self.offsets.removal_range(item.span()),
+                    }),
+                    None => self.roots.extend(tokens_of(item)),
+                },
+            }
+        }
+    }
+
+    fn add_impl(&mut self, item: &syn::ItemImpl) {
+        let self_ty = type_name(&item.self_ty);
+        let trait_ = item
+            .trait_
+            .as_ref()
+            .and_then(|(_, path, _)| Some(path.segments.last()?.ident.to_string()));
+        let condition = Condition::Impl {
+            self_ty,
+            trait_: trait_.clone(),
+        };
+        let range = self.offsets.removal_range(item.span());
+        if trait_.is_some() {
+            // A trait impl has to implement all of the trait's items
+            self.nodes.push(Node {
+                condition,
+                parent: None,
+                idents: tokens_of(item),
+                range,
+            });
+            return;
+        }
+        // The methods of an inherent impl are only kept if they are used
+        let mut idents = tokens_of(&item.generics);
+        idents.extend(tokens_of(&item.self_ty));
+        let parent = self.nodes.len();
+        self.nodes.push(Node {
+            condition,
+            parent: None,
+            idents,
+            range,
+        });
+        for impl_item in &item.items {
+            let name = match impl_item {
+                ImplItem::Const(item) => &item.ident,
+                ImplItem::Fn(item) => &item.sig.ident,
+                ImplItem::Type(item) => &item.ident,
+                _ => {
+                    self.nodes[parent].idents.extend(tokens_of(impl_item));
+                    continue;
+                }
+            };
+            self.nodes.push(Node {
+                condition: Condition::Named(vec![name.to_string()]),
+                parent: Some(parent),
+                idents: tokens_of(impl_item),
+                range: self.offsets.removal_range(impl_item.span()),
+            });
+        }
+    }
+
+    // Finds the reachable nodes, starting from the identifiers of the roots
+    fn reachable(&self) -> Vec<bool> {
+        let defined = self
+            .nodes
+            .iter()
+            .filter_map(|node| match &node.condition {
+                Condition::Named(names) => Some(names),
+                Condition::Impl { .. } => None,
+            })
+            .flatten()
+            .collect::<HashSet<_>>();
+        let mut mentioned = self.roots.clone();
+        let mut reachable = vec![false; self.nodes.len()];
+        let is_met = |name: &Option<String>, mentioned: &HashSet<String>| match name {
+            Some(name) => !defined.contains(name) || mentioned.contains(name),
+            None => true,
+        };
+        loop {
+            let mut changed = false;
+            for (i, node) in self.nodes.iter().enumerate() {
+                if reachable[i] || node.parent.is_some_and(|parent| !reachable[parent]) {
+                    continue;
+                }
+                let met = match &node.condition {
+                    Condition::Named(names) => names.iter().any(|name| mentioned.contains(name)),
+                    Condition::Impl { self_ty, trait_ } => {
+                        is_met(self_ty, &mentioned) && is_met(trait_, &mentioned)
+                    }
+                };
+                if met {
+                    reachable[i] = true;
+                    mentioned.extend(node.idents.iter().cloned());
+                    changed = true;
+                }
+            }
+            if !changed {
+                return reachable;
+            }
+        }
+    }
+}
+
+// Removes the library items that the solution does not use, directly or through other library items.
+// Items are matched by name only, so an item is kept whenever something with its name is used.
+fn remove_unreachable(source: &str, libs: &[String]) -> Result<String> {
+    let file = parse(source, "<bundled solution>".as_ref())?;
+    let offsets = Offsets::new(source);
+    let mut graph = Graph {
+        offsets: &offsets,
+        nodes: vec![],
+        roots: HashSet::new(),
+    };
+    for attr in &file.attrs {
+        graph.roots.extend(tokens_of(attr));
+    }
+    for item in &file.items {
+        match item {
+            Item::Mod(module) if libs.contains(&module.ident.to_string()) => {
+                graph.add_items(std::slice::from_ref(item));
+            }
+            item => graph.roots.extend(tokens_of(item)),
+        }
+    }
+    let reachable = graph.reachable();
+    let edits = graph
+        .nodes
+        .into_iter()
+        .zip(reachable)
+        .filter(|(_, reachable)| !reachable)
+        .map(|(node, _)| (node.range, String::new()))
+        .collect();
+    Ok(apply_edits(source, edits))
+}
+
+// Collects the ranges of the tokens, except for doc comments, which are left in between them
+fn token_ranges(source: &str, offsets: &Offsets, tokens: TokenStream, ranges: &mut Vec<Range<usize>>) {
+    let tokens = tokens.into_iter().collect::<Vec<_>>();
+    let mut i = 0;
+    while i < tokens.len() {
+        let range = match &tokens[i] {
+            TokenTree::Group(group) => {
+                ranges.push(offsets.range(group.span_open()));
+                token_ranges(source, offsets, group.stream(), ranges);
+                offsets.range(group.span_close())
+            }
+            token => offsets.range(token.span()),
+        };
+        // A doc comment is parsed into `#[doc = "..."]` tokens that all point at the comment
+        if source[range.start..].starts_with("//") || source[range.start..].starts_with("/*") {
+            while tokens.get(i).is_some_and(|token| offsets.range(token.span()) == range) {
+                i += 1;
+            }
+            continue;
+        }
+        ranges.push(range);
+        i += 1;
+    }
+}
+
+// Removes the comments from the whitespace between two tokens.
+// Lines holding only comments are removed, and the tokens are kept apart.
+fn strip_gap(gap: &str) -> String {
+    let mut depth = 0;
+    let mut stripped = String::new();
+    let lines = gap.split_inclusive('\n').collect::<Vec<_>>();
+    for (i, line) in lines.iter().enumerate() {
+        let (line, newline) = match line.strip_suffix('\n') {
+            Some(line) => (line, "\n"),
+            None => (*line, ""),
+        };
+        let mut kept = String::new();
+        let mut rest = line;
+        while !rest.is_empty() {
+            if depth > 0 {
+                if let Some(after) = rest.strip_prefix("/*") {
+                    depth += 1;
+                    rest = after;
+                } else if let Some(after) = rest.strip_prefix("*/") {
+                    depth -= 1;
+                    rest = after;
+                } else {
+                    let c = rest.chars().next().unwrap();
+                    rest = &rest[c.len_utf8()..];
+                }
+            } else if rest.starts_with("//") {
+                rest = "";
+            } else if let Some(after) = rest.strip_prefix("/*") {
+                depth = 1;
+                rest = after;
+            } else {
+                let c = rest.chars().next().unwrap();
+                kept.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+        // The first line continues a line with a token on it
+        if i > 0 && !newline.is_empty() && kept.trim().is_empty() && !line.trim().is_empty() {
+            continue;
+        }
+        if newline.is_empty() {
+            stripped.push_str(&kept);
+        } else {
+            stripped.push_str(kept.trim_end());
+            stripped.push_str(newline);
+        }
+    }
+    if stripped.is_empty() && !gap.is_empty() {
+        stripped.push(' ');
+    }
+    stripped
+}
+
+fn strip_comments(source: &str, libs: &[String]) -> Result<String> {
+    let file = parse(source, "<bundled solution>".as_ref())?;
+    let offsets = Offsets::new(source);
+    let lib_ranges = file
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Mod(module) if libs.contains(&module.ident.to_string()) => {
+                Some(offsets.range(module.span()))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let mut ranges = vec![];
+    token_ranges(source, &offsets, TokenStream::from_str(source)?, &mut ranges);
+    ranges.sort_by_key(|range| range.start);
+    let edits = ranges
+        .windows(2)
+        .map(|pair| pair[0].end..pair[1].start)
+        .filter(|gap| gap.start < gap.end)
+        .filter(|gap| {
+            lib_ranges
+                .iter()
+                .any(|lib| lib.start <= gap.start && gap.end <= lib.end)
+        })
+        .filter(|gap| source[gap.clone()].contains("//") || source[gap.clone()].contains("/*"))
+        .map(|gap| {
+            let stripped = strip_gap(&source[gap.clone()]);
+            (gap, stripped)
+        })
+        .collect();
+    Ok(apply_edits(source, edits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn minified(source: &str) -> String {
+        let bundled = Bundled {
+            source: source.to_string(),
+            libs: vec!["lib".to_string()],
+        };
+        minify(&bundled).unwrap()
+    }
+
+    #[test]
+    fn prunes_unused_methods_of_inherent_impls() {
+        let source = r#"fn main() {
+    let tree = crate::lib::SegTree::new(4);
+    tree.query();
+}
+
+mod lib {
+    pub struct SegTree(Vec<u64>);
+    impl SegTree {
+        pub fn new(n: usize) -> Self {
+            SegTree(vec![0; n])
+        }
+        pub fn query(&self) -> u64 {
+            self.combine()
+        }
+        fn combine(&self) -> u64 {
+            0
+        }
+        pub fn update(&mut self) {}
+    }
+    pub struct Unused;
+    impl Unused {
+        pub fn new() -> Self {
+            Unused
+        }
+    }
+}
+"#;
+        let minified = minified(source);
+        assert!(minified.contains("pub fn new(n: usize)"));
+        assert!(minified.contains("fn combine"));
+        assert!(!minified.contains("fn update"));
+        assert!(!minified.contains("Unused"));
+    }
+
+    #[test]
+    fn keeps_trait_impls_of_used_types() {
+        let source = r#"fn main() {
+    println!("{}", crate::lib::Mint(1) + crate::lib::Mint(2));
+}
+
+mod lib {
+    use std::fmt;
+    use std::ops::Add;
+    pub struct Mint(pub u64);
+    impl Add for Mint {
+        type Output = Mint;
+        fn add(self, other: Mint) -> Mint {
+            Mint(self.0 + other.0)
+        }
+    }
+    impl fmt::Display for Mint {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+    pub struct Unused;
+    impl fmt::Display for Unused {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "unused")
+        }
+    }
+}
+"#;
+        let minified = minified(source);
+        assert!(minified.contains("impl Add for Mint"));
+        assert!(minified.contains("impl fmt::Display for Mint"));
+        assert!(!minified.contains("Unused"));
+    }
+
+    #[test]
+    fn keeps_used_exported_macros_and_what_they_call() {
+        let source = r#"fn main() {
+    let n: usize = input!();
+}
+
+#[allow(unused)]
+mod lib {
+    #[macro_export]
+    macro_rules! input {
+        () => {
+            $crate::lib::read_line().parse().unwrap()
+        };
+    }
+    #[macro_export]
+    macro_rules! unused {
+        () => {
+            $crate::lib::write_line()
+        };
+    }
+    pub fn read_line() -> String {
+        String::new()
+    }
+    pub fn write_line() {}
+}
+"#;
+        let minified = minified(source);
+        assert!(minified.contains("macro_rules! input"));
+        assert!(minified.contains("fn read_line"));
+        assert!(!minified.contains("macro_rules! unused"));
+        assert!(!minified.contains("fn write_line"));
+    }
+
+    #[test]
+    fn follows_glob_and_self_reexports() {
+        let source = r#"use crate::lib::*;
+
+fn main() {
+    let _ = gcd(4, 6);
+    let _ = Fenwick::new();
+}
+
+mod lib {
+    pub use self::math::*;
+    pub use self::tree::Fenwick;
+    pub use self::tree::Unused;
+    pub mod math {
+        pub fn gcd(a: u64, b: u64) -> u64 {
+            if b == 0 { a } else { gcd(b, a % b) }
+        }
+        pub fn lcm(a: u64, b: u64) -> u64 {
+            a / gcd(a, b) * b
+        }
+    }
+    pub mod tree {
+        pub struct Fenwick;
+        impl Fenwick {
+            pub fn new() -> Self {
+                Fenwick
+            }
+        }
+        pub struct Unused;
+    }
+}
+"#;
+        let minified = minified(source);
+        assert!(minified.contains("pub use self::math::*;"));
+        assert!(minified.contains("pub use self::tree::Fenwick;"));
+        assert!(minified.contains("fn gcd"));
+        assert!(minified.contains("pub struct Fenwick"));
+        assert!(!minified.contains("fn lcm"));
+        assert!(!minified.contains("Unused"));
+    }
+
+    #[test]
+    fn strips_doc_comments_and_doc_tests_of_libraries_only() {
+        let source = r#"// Reads the input
+fn main() {
+    println!("{}", crate::lib::double(2));
+}
+
+mod lib {
+    //! Arithmetic helpers
+    /// Doubles a number.
+    ///
+    /// ```
+    /// assert_eq!(lib::double(2), 4);
+    /// ```
+    pub fn double(x: u64) -> u64 {
+        // Shifting would work too
+        x * 2
+    }
+}
+"#;
+        let minified = minified(source);
+        assert!(minified.starts_with("// Reads the input\n"));
+        assert!(minified.contains("pub fn double(x: u64) -> u64 {\n        x * 2\n    }"));
+        assert!(!minified.contains("Arithmetic"));
+        assert!(!minified.contains("Doubles"));
+        assert!(!minified.contains("assert_eq"));
+        assert!(!minified.contains("Shifting"));
+    }
+
+    #[test]
+    fn keeps_comment_markers_in_string_literals() {
+        let source = r#"fn main() {
+    println!("{}{}", crate::lib::URL, crate::lib::PATTERN);
+}
+
+mod lib {
+    pub const URL: &str = "https://www.acmicpc.net"; // The judge
+    pub const PATTERN: &str = "/* not a comment */";
+}
+"#;
+        let minified = minified(source);
+        assert!(minified.contains(r#"pub const URL: &str = "https://www.acmicpc.net";"#));
+        assert!(minified.contains(r#"pub const PATTERN: &str = "/* not a comment */";"#));
+        assert!(!minified.contains("The judge"));
+    }
+
+    #[test]
+    fn strips_nested_block_comments() {
+        let source = r#"fn main() {
+    crate::lib::solve();
+}
+
+mod lib {
+    /* outer /* inner */ still a comment */
+    pub fn solve() {
+        let x = 1 /* a /* b */ c */ + 2;
+        println!("{}", x);
+    }
+}
+"#;
+        let minified = minified(source);
+        assert!(!minified.contains("comment"));
+        assert!(!minified.contains("inner"));
+        // The comment between the tokens leaves only whitespace behind
+        assert!(minified
+            .lines()
+            .any(|line| line.split_whitespace().collect::<Vec<_>>() == ["let", "x", "=", "1", "+", "2;"]));
+    }
+}
@@ -1,17 +1,25 @@
 use std::error::Error;
 use std::fmt;
+use std::path::PathBuf;
 use std::str::FromStr;
 
 use bpaf::batteries::cargo_helper;
 use bpaf::*;
 
+use crate::checker::Checker;
+use crate::compare::{Compare, DiffStyle};
 use crate::datastore::Cookies;
 use crate::datastore::LanguageTypes;
+use crate::report::Format;
 
 pub enum Opts {
     Login(Login),
     Test(Test),
     Submit(Submit),
+    Case(Case),
+    Stress(Stress),
+    Bundle(Bundle),
+    History(History),
 }
 
 #[derive(Clone)]
@@ -19,18 +27,126 @@ pub struct Login {
     pub cookies: Option<Cookies>,
 }
 
+#[derive(Clone)]
 pub struct Test {
     pub problem_id: String,
     pub bin_or_cmd: Option<BinOrCmd>,
     pub spj_prompt: bool,
     pub refresh: bool,
+    pub time_factor: f64,
+    pub memory_rlimit: bool,
+    pub checker: Option<Checker>,
+    pub compare: Option<Compare>,
+    pub diff_style: DiffStyle,
+    pub format: Format,
+    // None means following the settings file
+    pub keep_going: Option<bool>,
+    pub watch: bool,
+    pub path: Option<String>,
+    pub build: Build,
+    pub language: Option<LanguageType>,
+    pub boj_env: bool,
+    pub boj_toolchain: bool,
+    pub no_bundle: bool,
+    pub interactor: Option<String>,
+    pub two_steps: Option<TwoSteps>,
+    pub jobs: usize,
+}
+
+pub struct Stress {
+    pub problem_id: String,
+    pub bin_or_cmd: Option<BinOrCmd>,
+    pub generator: BinOrCmd,
+    pub brute: BinOrCmd,
+    pub iterations: usize,
+    pub seed: Option<u64>,
+    pub size: Option<usize>,
+    pub no_shrink: bool,
+    pub build: Build,
+}
+
+pub enum Case {
+    Add(CaseAdd),
+    List(CaseList),
+}
+
+pub struct CaseAdd {
+    pub problem_id: String,
+    pub name: Option<String>,
+    pub input: Option<PathBuf>,
+    pub output: Option<PathBuf>,
+}
+
+pub struct CaseList {
+    pub problem_id: String,
+}
+
+// How Rust solutions are built with cargo
+#[derive(Clone)]
+pub struct Build {
+    // Defaults to dev with `debug`, release otherwise
+    pub profile: Option<String>,
+    // Build with the dev profile and show backtraces of panics
+    pub debug: bool,
+    // Enable overflow checks and debug assertions on top of the profile
+    pub checks: bool,
+    pub features: Option<String>,
+    pub diagnostics: Diagnostics,
+}
+
+// What is shown when a build fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Diagnostics {
+    // cargo's output as is
+    Full,
+    // Only the errors, one line each
+    Errors,
 }
 
+impl FromStr for Diagnostics {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "full" => Ok(Self::Full),
+            "errors" => Ok(Self::Errors),
+            _ => Err("expected `full` or `errors`".to_string()),
+        }
+    }
+}
+
+// How the two runs of a Two Step problem are told apart
+#[derive(Clone)]
+pub enum TwoSteps {
+    // Feed the first run's output to the second run as is
+    Plain,
+    // Pass the step number (1 or 2) as a command-line argument
+    Arg,
+    // Prepend a line to the input of each run
+    Line(String, String),
+}
+
+impl FromStr for TwoSteps {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split(':').collect::<Vec<_>>()[..] {
+            ["plain"] => Ok(Self::Plain),
+            ["arg"] => Ok(Self::Arg),
+            ["line"] => Ok(Self::Line("1".to_string(), "2".to_string())),
+            ["line", first, second] => Ok(Self::Line(first.to_string(), second.to_string())),
+            _ => Err("expected `plain`, `arg`, `line` or `line:<FIRST>:<SECOND>`".to_string()),
+        }
+    }
+}
+
+#[derive(Clone)]
 pub enum BinOrCmd {
     Bin(String),
     Cmd(String),
 }
 
+#[derive(Clone)]
 pub enum LanguageType {
     Id(usize),
     Name(String),
@@ -53,6 +169,17 @@ fn get_language_id_from_str(s: &str) -> usize {
     }
 }
 
+// The name of a language in the language list, e.g. 113 -> "Rust 2021"
+pub fn get_language_name(id: usize) -> Option<String> {
+    let language_types = LanguageTypes::load();
+    let serde_json::Value::Object(map) = language_types.language_types else {
+        return None;
+    };
+    map.into_iter()
+        .find(|(_, value)| value.as_u64() == Some(id as u64))
+        .map(|(name, _)| name)
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct LanguageTypeError;
 
@@ -86,6 +213,21 @@ pub struct Submit {
     pub path: Option<String>,
     pub language: Option<LanguageType>,
     pub code_open: Option<CodeOpen>,
+    pub no_bundle: bool,
+    // None means following the settings file
+    pub test_first: Option<bool>,
+}
+
+pub struct Bundle {
+    pub path: Option<String>,
+    pub output: Option<String>,
+}
+
+pub struct History {
+    pub problem_id: Option<String>,
+    pub verdict: Option<String>,
+    pub language: Option<LanguageType>,
+    pub limit: Option<usize>,
 }
 
 pub enum CodeOpen {
@@ -122,14 +264,14 @@ impl FromStr for CodeOpen {
     }
 }
 
-impl ToString for CodeOpen {
-    fn to_string(&self) -> String {
+impl fmt::Display for CodeOpen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             CodeOpen::Yes => "open",
             CodeOpen::No => "close",
             CodeOpen::YesOnAc => "onlyaccepted",
         }
-        .to_string()
+        .fmt(f)
     }
 }
 
@@ -137,7 +279,11 @@ pub fn cargo_boj_opts() -> Opts {
     let login = construct!(Opts::Login(cargo_boj_login()));
     let test = construct!(Opts::Test(cargo_boj_test()));
     let submit = construct!(Opts::Submit(cargo_boj_submit()));
-    cargo_helper("boj", construct!([login, test, submit]))
+    let case = construct!(Opts::Case(cargo_boj_case()));
+    let stress = construct!(Opts::Stress(cargo_boj_stress()));
+    let bundle = construct!(Opts::Bundle(cargo_boj_bundle()));
+    let history = construct!(Opts::History(cargo_boj_history()));
+    cargo_helper("boj", construct!([login, test, submit, case, stress, bundle, history]))
         .to_options()
         .run()
 }
@@ -160,6 +306,35 @@ fn cargo_boj_login() -> impl Parser<Login> {
         .command("login")
 }
 
+fn build_opts() -> impl Parser<Build> {
+    let profile = long("profile")
+        .help("Cargo profile to build Rust solutions with, e.g. dev for overflow checks. Defaults to release")
+        .argument("PROFILE")
+        .optional();
+    let debug = short('d')
+        .long("debug")
+        .help("Build Rust solutions with the dev profile, and show backtraces of panics")
+        .switch();
+    let checks = long("checks")
+        .help("Enable overflow checks and debug assertions in any profile, and show backtraces of panics")
+        .switch();
+    let features = long("features")
+        .help("Cargo features to enable when building Rust solutions")
+        .argument("FEATURES")
+        .optional();
+    let diagnostics = long("diagnostics")
+        .help("What to show when the build fails. Options are: full, errors")
+        .argument("MODE")
+        .fallback(Diagnostics::Full);
+    construct!(Build {
+        profile,
+        debug,
+        checks,
+        features,
+        diagnostics
+    })
+}
+
 fn cargo_boj_test() -> impl Parser<Test> {
     let problem_id = positional("PID").help("Problem ID");
     let bin = short('b')
@@ -178,6 +353,77 @@ fn cargo_boj_test() -> impl Parser<Test> {
         .long("refresh")
         .help("If set, refresh the cache for the problem")
         .switch();
+    let time_factor = short('t')
+        .long("time-factor")
+        .help("Multiply the problem's time limit by this factor")
+        .argument::<f64>("FACTOR")
+        .guard(|f| f.is_finite() && *f > 0.0, "The time factor must be a positive number")
+        .fallback(1.0);
+    let memory_rlimit = short('m')
+        .long("memory-rlimit")
+        .help("If set, enforce the memory limit with an rlimit (Linux only)")
+        .switch();
+    let checker = long("checker")
+        .help("Checker to judge the output with. Options are: float[:EPS[:REL]], token, unordered, nocase, or a testlib-style checker command")
+        .argument("CHECKER")
+        .optional();
+    let compare = long("compare")
+        .help("How to compare the output. Options are: exact, line-trim, token, float:EPS. Remembered for the problem")
+        .argument("MODE")
+        .optional();
+    let diff_style = long("diff-style")
+        .help("How to show a mismatching output. Options are: unified, inline, side-by-side, first")
+        .argument("STYLE")
+        .fallback(DiffStyle::Unified);
+    let format = long("format")
+        .help("Output format of the results. Options are: text, json, junit")
+        .argument("FORMAT")
+        .fallback(Format::Text);
+    let keep_going = short('k')
+        .long("keep-going")
+        .help("Run all test cases even after one fails, and show a summary")
+        .req_flag(true);
+    let no_keep_going = long("no-keep-going")
+        .help("Stop at the first failing test case, overriding the settings file")
+        .req_flag(false);
+    let keep_going = construct!([keep_going, no_keep_going]).optional();
+    let watch = short('w')
+        .long("watch")
+        .help("Re-run the tests whenever the solution's source file changes")
+        .switch();
+    let path = long("path")
+        .help("Path of the solution's source file to test and watch. Non-Rust files are built with their language profile")
+        .argument("PATH")
+        .optional();
+    let build = build_opts();
+    let language = short('l')
+        .long("lang")
+        .help("Language ID or name the solution is for. Defaults to 113 (Rust 2021)")
+        .argument("LANG")
+        .optional();
+    let boj_env = long("boj-env")
+        .help("Compile the source file with rustc the way BOJ does for the language, instead of cargo")
+        .switch();
+    let boj_toolchain = long("boj-toolchain")
+        .help("With --boj-env, compile with BOJ's rustc version through rustup")
+        .switch();
+    let no_bundle = long("no-bundle")
+        .help("With --boj-env, compile the file as is instead of bundling its modules and local crates into it")
+        .switch();
+    let interactor = short('i')
+        .long("interactor")
+        .help("Command to run an interactor, connected to the solution's stdin and stdout")
+        .argument("CMD")
+        .optional();
+    let two_steps = long("two-steps")
+        .help("Run the solution twice, feeding the first output to the second run. Options are: plain, arg, line[:FIRST:SECOND]")
+        .argument("MODE")
+        .optional();
+    let jobs = short('j')
+        .long("jobs")
+        .help("Number of test cases to run in parallel. Use 1 for the most accurate timings")
+        .argument("N")
+        .fallback(1);
     let bin = construct!(BinOrCmd::Bin(bin));
     let cmd = construct!(BinOrCmd::Cmd(cmd));
     let bin_or_cmd = construct!([bin, cmd]).optional();
@@ -185,6 +431,23 @@ fn cargo_boj_test() -> impl Parser<Test> {
         bin_or_cmd,
         spj_prompt,
         refresh,
+        time_factor,
+        memory_rlimit,
+        checker,
+        compare,
+        diff_style,
+        format,
+        keep_going,
+        watch,
+        path,
+        build,
+        language,
+        boj_env,
+        boj_toolchain,
+        no_bundle,
+        interactor,
+        two_steps,
+        jobs,
         problem_id
     })
     .to_options()
@@ -209,13 +472,184 @@ fn cargo_boj_submit() -> impl Parser<Submit> {
         .help("Whether to open code to public. Options are: y(yes), n(no), ac(yes on AC)")
         .argument("OPT")
         .optional();
+    let no_bundle = long("no-bundle")
+        .help("If set, submit a Rust file as is instead of bundling its modules and local crates into it")
+        .switch();
+    let test_first = long("test-first")
+        .help("Test the source to submit against the example tests first, and submit only if they pass")
+        .req_flag(true);
+    let no_test_first = long("no-test-first")
+        .help("Submit without testing first, overriding the settings file")
+        .req_flag(false);
+    let test_first = construct!([test_first, no_test_first]).optional();
     construct!(Submit {
         path,
         language,
         code_open,
+        no_bundle,
+        test_first,
         problem_id,
     })
     .to_options()
     .descr("Submit a solution to a BOJ problem.")
     .command("submit")
 }
+
+fn cargo_boj_case() -> impl Parser<Case> {
+    let add = construct!(Case::Add(cargo_boj_case_add()));
+    let list = construct!(Case::List(cargo_boj_case_list()));
+    construct!([add, list])
+        .to_options()
+        .descr("Manage custom test cases stored in tests/<PID>/.")
+        .command("case")
+}
+
+fn cargo_boj_case_add() -> impl Parser<CaseAdd> {
+    let problem_id = positional("PID").help("Problem ID");
+    let name = short('n')
+        .long("name")
+        .help("Name of the case. Defaults to the next unused number")
+        .argument("NAME")
+        .optional();
+    let input = short('i')
+        .long("input")
+        .help("File to read the input from. If not set, $EDITOR is opened")
+        .argument("FILE")
+        .optional();
+    let output = short('o')
+        .long("output")
+        .help("File to read the expected output from. If not set, $EDITOR is opened")
+        .argument("FILE")
+        .optional();
+    construct!(CaseAdd {
+        name,
+        input,
+        output,
+        problem_id,
+    })
+    .to_options()
+    .descr("Add a custom test case for a problem.")
+    .command("add")
+}
+
+fn cargo_boj_case_list() -> impl Parser<CaseList> {
+    let problem_id = positional("PID").help("Problem ID");
+    construct!(CaseList { problem_id })
+        .to_options()
+        .descr("List custom test cases for a problem.")
+        .command("list")
+}
+
+fn cargo_boj_stress() -> impl Parser<Stress> {
+    let problem_id = positional("PID").help("Problem ID");
+    let bin = short('b')
+        .long("bin")
+        .help("Bin name of the solution in the current Rust crate")
+        .argument("BIN");
+    let cmd = short('c')
+        .long("cmd")
+        .help("Command to run a non-Rust solution")
+        .argument("CMD");
+    let gen_bin = short('g')
+        .long("gen")
+        .help("Bin name of the input generator, which gets the seed as its argument")
+        .argument("BIN");
+    let gen_cmd = long("gen-cmd")
+        .help("Command to run the input generator, which gets the seed as its argument")
+        .argument("CMD");
+    let brute_bin = short('B')
+        .long("brute")
+        .help("Bin name of the reference solution")
+        .argument("BIN");
+    let brute_cmd = long("brute-cmd")
+        .help("Command to run the reference solution")
+        .argument("CMD");
+    let iterations = short('n')
+        .long("iterations")
+        .help("Number of random tests to run")
+        .argument("N")
+        .fallback(100);
+    let seed = short('s')
+        .long("seed")
+        .help("Seed of the first test. Defaults to the current time")
+        .argument("SEED")
+        .optional();
+    let size = long("size")
+        .help("Size parameter passed to the generator after the seed. Smaller sizes are tried when shrinking")
+        .argument("SIZE")
+        .optional();
+    let no_shrink = long("no-shrink")
+        .help("If set, save the failing input as is instead of shrinking it")
+        .switch();
+    let bin = construct!(BinOrCmd::Bin(bin));
+    let cmd = construct!(BinOrCmd::Cmd(cmd));
+    let bin_or_cmd = construct!([bin, cmd]).optional();
+    let gen_bin = construct!(BinOrCmd::Bin(gen_bin));
+    let gen_cmd = construct!(BinOrCmd::Cmd(gen_cmd));
+    let generator = construct!([gen_bin, gen_cmd]);
+    let brute_bin = construct!(BinOrCmd::Bin(brute_bin));
+    let brute_cmd = construct!(BinOrCmd::Cmd(brute_cmd));
+    let brute = construct!([brute_bin, brute_cmd]);
+    let build = build_opts();
+    construct!(Stress {
+        bin_or_cmd,
+        generator,
+        brute,
+        iterations,
+        seed,
+        size,
+        no_shrink,
+        build,
+        problem_id,
+    })
+    .to_options()
+    .descr("Stress test a solution against a reference solution on generated inputs.")
+    .command("stress")
+}
+
+fn cargo_boj_bundle() -> impl Parser<Bundle> {
+    let path = short('p')
+        .long("path")
+        .help("Path of the Rust file to bundle. Defaults to src/main.rs or src/bin/main.rs")
+        .argument("PATH")
+        .optional();
+    let output = short('o')
+        .long("output")
+        .help("File to write the bundled solution to. If not set, it is printed")
+        .argument("FILE")
+        .optional();
+    construct!(Bundle { path, output })
+        .to_options()
+        .descr("Bundle a Rust solution with its modules and local library crates into a single file.")
+        .command("bundle")
+}
+
+fn cargo_boj_history() -> impl Parser<History> {
+    let verdict = short('v')
+        .long("verdict")
+        .help("Only list submissions with this verdict, e.g. AC, WA or \"Time Limit Exceeded\"")
+        .argument("VERDICT")
+        .optional();
+    let language = short('l')
+        .long("lang")
+        .help("Only list submissions in this language ID or name")
+        .argument("LANG")
+        .optional();
+    let limit = short('n')
+        .long("limit")
+        .help("Only list this many of the latest submissions")
+        .argument("N")
+        .optional();
+    let problem_id = positional("PID")
+        .help("Only list submissions to this problem")
+        .optional();
+    construct!(History {
+        verdict,
+        language,
+        limit,
+        problem_id,
+    })
+    .to_options()
+    .descr("List the solutions submitted with `cargo boj submit`, latest first.")
+    .command("history")
+}
@@ -0,0 +1,271 @@
+use std::fmt::Write;
+use std::str::FromStr;
+use std::time::Duration;
+
+use console::Style;
+use serde::Serialize;
+
+use crate::test::{format_elapsed, CaseResult, Source, TestCase, Verdict};
+use crate::Result;
+
+// How `cargo boj test` reports its results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    // Colored text for humans
+    Text,
+    Json,
+    // JUnit XML, understood by most CI services
+    Junit,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            "junit" => Ok(Self::Junit),
+            _ => Err("expected `text`, `json` or `junit`".to_string()),
+        }
+    }
+}
+
+// The result of a test case in machine-readable form
+#[derive(Serialize)]
+pub struct CaseRecord {
+    pub id: String,
+    pub name: String,
+    pub source: Source,
+    // None if the case was not run because an earlier case failed
+    pub verdict: Option<Verdict>,
+    // In seconds
+    pub elapsed: Option<f64>,
+    // In kilobytes
+    pub memory: Option<u64>,
+    // The outputs are only included for cases that did not pass
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actual: Option<String>,
+    // Set when the case could not be judged, e.g. because the checker failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl CaseRecord {
+    pub fn new(case: &TestCase, result: Option<CaseResult>) -> Self {
+        let mut record = Self {
+            id: case.id.clone(),
+            name: case.label(),
+            source: case.source,
+            verdict: None,
+            elapsed: None,
+            memory: None,
+            expected: None,
+            actual: None,
+            error: None,
+        };
+        match result {
+            Some(Ok(outcome)) => {
+                record.verdict = Some(outcome.verdict);
+                record.elapsed = Some(outcome.elapsed.as_secs_f64());
+                record.memory = outcome.memory;
+                if outcome.verdict != Verdict::Accepted {
+                    record.expected = Some(case.output.clone());
+                    record.actual = Some(outcome.stdout);
+                }
+            }
+            Some(Err(error)) => record.error = Some(error),
+            None => {}
+        }
+        record
+    }
+
+    // Whether the case definitely failed. An unjudged Special Judge output is not a failure.
+    pub fn failed(&self) -> bool {
+        self.error.is_some()
+            || self
+                .verdict
+                .is_some_and(|verdict| !matches!(verdict, Verdict::Accepted | Verdict::Unknown))
+    }
+}
+
+fn abbreviation(verdict: Verdict) -> &'static str {
+    match verdict {
+        Verdict::Accepted => "AC",
+        Verdict::WrongAnswer => "WA",
+        Verdict::TimeLimitExceeded => "TLE",
+        Verdict::MemoryLimitExceeded => "MLE",
+        Verdict::RuntimeError => "RTE",
+        Verdict::Unknown => "SPJ?",
+    }
+}
+
+// Prints a table of the cases' results, followed by the overall verdict:
+// the verdict of the first failing case, or Unknown if some Special Judge output was not judged.
+pub fn print_summary(records: &[CaseRecord]) {
+    let name_width = records
+        .iter()
+        .map(|r| r.name.chars().count())
+        .chain(["Case".len()])
+        .max()
+        .unwrap();
+    println!();
+    println!(
+        "{}",
+        Style::new().bold().apply_to(format!(
+            "{:<name_width$}  {:<7}  {:<9}  Memory",
+            "Case", "Verdict", "Time"
+        ))
+    );
+    for record in records {
+        let (verdict, style) = match (record.verdict, &record.error) {
+            (_, Some(_)) => ("ERR", Style::new().red()),
+            (None, _) => ("-", Style::new().dim()),
+            (Some(Verdict::Accepted), _) => ("AC", Style::new().green()),
+            (Some(Verdict::Unknown), _) => ("SPJ?", Style::new().yellow()),
+            (Some(verdict), _) => (abbreviation(verdict), Style::new().red()),
+        };
+        let time = record
+            .elapsed
+            .map(|elapsed| format_elapsed(Duration::from_secs_f64(elapsed)))
+            .unwrap_or_else(|| "-".to_string());
+        let memory = record
+            .memory
+            .map(|memory| format!("{} KB", memory))
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "{:<name_width$}  {}  {:<9}  {}",
+            record.name,
+            style.apply_to(format!("{:<7}", verdict)),
+            time,
+            memory
+        );
+    }
+    let passed = records
+        .iter()
+        .filter(|r| r.verdict == Some(Verdict::Accepted))
+        .count();
+    let overall = match records.iter().find(|r| r.failed()) {
+        Some(CaseRecord {
+            error: Some(_), ..
+        }) => Style::new().red().apply_to("Error".to_string()),
+        Some(record) => Style::new().red().apply_to(record.verdict.unwrap().to_string()),
+        None if records.iter().any(|r| r.verdict == Some(Verdict::Unknown)) => {
+            Style::new().yellow().apply_to("Unknown (Special Judge)".to_string())
+        }
+        None => Style::new().green().apply_to(Verdict::Accepted.to_string()),
+    };
+    println!("Overall: {} ({}/{} passed)", overall, passed, records.len());
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    problem_id: &'a str,
+    cases: &'a [CaseRecord],
+}
+
+pub fn print_json(problem_id: &str, records: &[CaseRecord]) -> Result<()> {
+    let report = Report {
+        problem_id,
+        cases: records,
+    };
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
+
+// Escapes text for XML, dropping control characters XML 1.0 can't represent
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub fn print_junit(problem_id: &str, records: &[CaseRecord]) {
+    let is_failure = |record: &&CaseRecord| {
+        record
+            .verdict
+            .is_some_and(|verdict| !matches!(verdict, Verdict::Accepted | Verdict::Unknown))
+    };
+    let failures = records.iter().filter(is_failure).count();
+    let errors = records.iter().filter(|r| r.error.is_some()).count();
+    let skipped = records
+        .iter()
+        .filter(|r| matches!(r.verdict, None | Some(Verdict::Unknown)) && r.error.is_none())
+        .count();
+    let time = records.iter().filter_map(|r| r.elapsed).sum::<f64>();
+
+    // Writing to a String can't fail
+    let mut xml = String::new();
+    writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    writeln!(
+        xml,
+        r#"<testsuite name="boj {}" tests="{}" failures="{}" errors="{}" skipped="{}" time="{:.6}">"#,
+        escape(problem_id),
+        records.len(),
+        failures,
+        errors,
+        skipped,
+        time
+    )
+    .unwrap();
+    for record in records {
+        write!(
+            xml,
+            r#"  <testcase name="{}" classname="boj.{}" time="{:.6}""#,
+            escape(&record.name),
+            escape(problem_id),
+            record.elapsed.unwrap_or(0.0)
+        )
+        .unwrap();
+        match (&record.verdict, &record.error) {
+            (_, Some(error)) => {
+                writeln!(xml, ">").unwrap();
+                writeln!(xml, r#"    <error message="{}"/>"#, escape(error)).unwrap();
+            }
+            (Some(Verdict::Accepted), _) => {
+                writeln!(xml, "/>").unwrap();
+                continue;
+            }
+            (Some(Verdict::Unknown), _) => {
+                writeln!(xml, ">").unwrap();
+                writeln!(
+                    xml,
+                    r#"    <skipped message="The output differs from the expected output, but the problem is Special Judge"/>"#
+                )
+                .unwrap();
+            }
+            (Some(verdict), _) => {
+                writeln!(xml, ">").unwrap();
+                writeln!(
+                    xml,
+                    r#"    <failure message="{}" type="{:?}">Expected:"#,
+                    verdict, verdict
+                )
+                .unwrap();
+                writeln!(xml, "{}", escape(record.expected.as_deref().unwrap_or(""))).unwrap();
+                writeln!(xml, "Actual:").unwrap();
+                writeln!(xml, "{}</failure>", escape(record.actual.as_deref().unwrap_or(""))).unwrap();
+            }
+            (None, _) => {
+                writeln!(xml, ">").unwrap();
+                writeln!(xml, r#"    <skipped message="Not run after an earlier failure"/>"#).unwrap();
+            }
+        }
+        writeln!(xml, "  </testcase>").unwrap();
+    }
+    writeln!(xml, "</testsuite>").unwrap();
+    print!("{}", xml);
+}
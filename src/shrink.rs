@@ -0,0 +1,115 @@
+use std::io::Write;
+
+// Number of candidate inputs to try before giving up on shrinking further
+const SHRINK_BUDGET: usize = 1000;
+// Number of seeds to try for each smaller generator size
+const SEEDS_PER_SIZE: u64 = 20;
+
+// Searches for a smaller input on which the solution still fails.
+// `fails` runs the solution and the reference solution on a candidate and tells if they disagree;
+// it should return false for candidates the reference solution rejects as invalid.
+pub struct Shrinker<F: FnMut(&str) -> bool> {
+    fails: F,
+    runs: usize,
+}
+
+impl<F: FnMut(&str) -> bool> Shrinker<F> {
+    pub fn new(fails: F) -> Self {
+        Self { fails, runs: 0 }
+    }
+
+    fn try_candidate(&mut self, candidate: &str, best: &str) -> bool {
+        if self.runs >= SHRINK_BUDGET || candidate.len() >= best.len() {
+            return false;
+        }
+        self.runs += 1;
+        print!("\rShrinking: {} bytes ({} runs)", best.len(), self.runs);
+        let _ = std::io::stdout().flush();
+        (self.fails)(candidate)
+    }
+
+    // Tries halving the generator's size parameter, with a few seeds for each size.
+    pub fn shrink_size(
+        &mut self,
+        input: String,
+        size: usize,
+        seed: u64,
+        mut generate: impl FnMut(usize, u64) -> Option<String>,
+    ) -> String {
+        let mut best = input;
+        let mut size = size / 2;
+        while size > 0 && self.runs < SHRINK_BUDGET {
+            let found = (0..SEEDS_PER_SIZE).find_map(|i| {
+                let candidate = generate(size, seed.wrapping_add(i))?;
+                self.try_candidate(&candidate, &best).then_some(candidate)
+            });
+            match found {
+                Some(candidate) => best = candidate,
+                // The bug may not show up at all below this size
+                None => break,
+            }
+            size /= 2;
+        }
+        best
+    }
+
+    // Deletes chunks of lines, then tokens within each line, as long as the solution still fails.
+    // Lines and tokens are rejoined only in candidates, so the input is kept as is unless a deletion succeeds.
+    pub fn shrink_text(&mut self, input: String) -> String {
+        let lines = input.lines().map(|l| l.to_string()).collect::<Vec<_>>();
+        let (lines, mut best) = self.delete_chunks(lines, input, join_lines);
+        for (i, line) in lines.iter().enumerate() {
+            let tokens = line
+                .split_whitespace()
+                .map(|t| t.to_string())
+                .collect::<Vec<_>>();
+            let current = best.clone();
+            let make = |tokens: &[String]| {
+                let mut lines = current.lines().map(|l| l.to_string()).collect::<Vec<_>>();
+                lines[i] = tokens.join(" ");
+                join_lines(&lines)
+            };
+            best = self.delete_chunks(tokens, best, make).1;
+        }
+        best
+    }
+
+    // Removes chunks of items of decreasing size, keeping each removal that preserves the failure.
+    // Returns the remaining items and the last input that failed, which is `best` if no removal did.
+    fn delete_chunks(
+        &mut self,
+        mut items: Vec<String>,
+        mut best: String,
+        render: impl Fn(&[String]) -> String,
+    ) -> (Vec<String>, String) {
+        let mut chunk = items.len() / 2;
+        while chunk > 0 {
+            let mut i = 0;
+            while i < items.len() && self.runs < SHRINK_BUDGET {
+                let mut candidate_items = items.clone();
+                candidate_items.drain(i..(i + chunk).min(items.len()));
+                let candidate = render(&candidate_items);
+                if self.try_candidate(&candidate, &best) {
+                    items = candidate_items;
+                    best = candidate;
+                } else {
+                    i += chunk;
+                }
+            }
+            chunk /= 2;
+        }
+        (items, best)
+    }
+
+    pub fn finish(&self) {
+        if self.runs > 0 {
+            println!();
+        }
+    }
+}
+
+fn join_lines(lines: &[String]) -> String {
+    let mut s = lines.join("\n");
+    s.push('\n');
+    s
+}
@@ -0,0 +1,221 @@
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use console::Style;
+
+use crate::compare::{Compare, DiffStyle};
+use crate::datastore::{CustomCase, ProblemData};
+use crate::optparse::{BinOrCmd, Stress};
+use crate::shrink::Shrinker;
+use crate::test::{
+    build_program, default_bin, describe_status, execute, print_stderr, Execution, Limits, Output,
+    Program,
+};
+use crate::Result;
+
+const NO_LIMITS: Limits = Limits {
+    time: None,
+    memory: None,
+    enforce_memory: false,
+    jobs: 1,
+};
+
+// Runs the generator or the reference solution, which are expected to always succeed.
+fn run_helper(
+    program: &Program,
+    args: &[String],
+    input: &str,
+    name: &str,
+    seed: u64,
+) -> Result<String> {
+    let Execution {
+        stdout,
+        stderr,
+        status,
+        ..
+    } = execute(program, args, input, &NO_LIMITS);
+    if !status.success() {
+        let mut out = Output::default();
+        print_stderr(&mut out, &stderr);
+        out.flush();
+        Err(format!(
+            "Error: the {} failed with {} (seed {}).",
+            name,
+            describe_status(status),
+            seed
+        ))?
+    }
+    Ok(stdout)
+}
+
+// Checks the solution's run against the reference output, returning the reason of the failure.
+fn judge(execution: &Execution, expected: &str, compare: Compare) -> Option<String> {
+    if execution.timed_out {
+        Some("Time Limit Exceeded".to_string())
+    } else if !execution.status.success() {
+        Some(format!("Runtime Error ({})", describe_status(execution.status)))
+    } else if !compare.matches(&execution.stdout, expected) {
+        Some("Wrong Answer".to_string())
+    } else {
+        None
+    }
+}
+
+// The generator gets the seed, and the size if given, as its arguments
+fn generator_args(seed: u64, size: Option<usize>) -> Vec<String> {
+    let mut args = vec![seed.to_string()];
+    args.extend(size.map(|size| size.to_string()));
+    args
+}
+
+// The programs taking part in a stress test and how the solution is judged
+struct Programs {
+    solution: Program,
+    generator: Program,
+    brute: Program,
+    limits: Limits,
+    compare: Compare,
+}
+
+// Returns a smaller input on which the solution still disagrees with the reference solution.
+fn shrink(programs: &Programs, input: String, seed: u64, size: Option<usize>) -> String {
+    let Programs {
+        solution,
+        generator,
+        brute,
+        limits,
+        compare,
+    } = programs;
+    let original_len = input.len();
+    let mut shrinker = Shrinker::new(|candidate: &str| {
+        let reference = execute(brute, &[], candidate, &NO_LIMITS);
+        // An input the reference solution rejects is not a valid input
+        if !reference.status.success() {
+            return false;
+        }
+        let execution = execute(solution, &[], candidate, limits);
+        judge(&execution, &reference.stdout, *compare).is_some()
+    });
+    let mut input = input;
+    if let Some(size) = size {
+        input = shrinker.shrink_size(input, size, seed, |size, seed| {
+            let execution = execute(generator, &generator_args(seed, Some(size)), "", &NO_LIMITS);
+            execution.status.success().then_some(execution.stdout)
+        });
+    }
+    let input = shrinker.shrink_text(input);
+    shrinker.finish();
+    if input.len() < original_len {
+        println!("Shrunk the input from {} to {} bytes.", original_len, input.len());
+    }
+    input
+}
+
+pub fn stress(opts: Stress) -> Result<()> {
+    let Stress {
+        problem_id,
+        bin_or_cmd,
+        generator,
+        brute,
+        iterations,
+        seed,
+        size,
+        no_shrink,
+        build,
+    } = opts;
+    let solution = match bin_or_cmd {
+        Some(inner) => inner,
+        None => BinOrCmd::Bin(default_bin()?),
+    };
+    let solution = build_program(&solution, &build)?;
+    let generator = build_program(&generator, &build)?;
+    let brute = build_program(&brute, &build)?;
+    let ProblemData {
+        time_limit,
+        memory_limit,
+        compare,
+        ..
+    } = ProblemData::load(&problem_id, false);
+    let compare = compare.unwrap_or(Compare::LineTrim);
+    let limits = Limits {
+        time: time_limit,
+        memory: memory_limit,
+        enforce_memory: false,
+        jobs: 1,
+    };
+    let seed = seed.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    });
+
+    let programs = Programs {
+        solution,
+        generator,
+        brute,
+        limits,
+        compare,
+    };
+    let Programs {
+        solution,
+        generator,
+        brute,
+        limits,
+        compare,
+    } = &programs;
+
+    let mut stdout = std::io::stdout();
+    for i in 0..iterations {
+        let seed = seed.wrapping_add(i as u64);
+        print!("\rIteration {}/{} (seed {})", i + 1, iterations, seed);
+        stdout.flush()?;
+        let input = run_helper(generator, &generator_args(seed, size), "", "generator", seed)?;
+        let expected = run_helper(brute, &[], &input, "reference solution", seed)?;
+        let execution = execute(solution, &[], &input, limits);
+        if judge(&execution, &expected, *compare).is_none() {
+            continue;
+        }
+        println!();
+        let input = if no_shrink {
+            input
+        } else {
+            shrink(&programs, input, seed, size)
+        };
+        // Run the final input again to show the failure
+        let expected = run_helper(brute, &[], &input, "reference solution", seed)?;
+        let execution = execute(solution, &[], &input, limits);
+        let failure = judge(&execution, &expected, *compare);
+        let mut out = Output::default();
+        match &failure {
+            Some(failure) => out.eprintln(format_args!(
+                "{} on input (seed {}):",
+                Style::new().red().apply_to(failure),
+                seed
+            )),
+            // e.g. a timing that is close to the limit
+            None => out.eprintln(format_args!(
+                "{} when run again on input (seed {}):",
+                Style::new().yellow().apply_to("The failure did not reproduce"),
+                seed
+            )),
+        }
+        out.eprintln(&input);
+        if failure.is_some() && !execution.timed_out {
+            compare.print_diff(&mut out, &execution.stdout, &expected, true, DiffStyle::Unified);
+            print_stderr(&mut out, &execution.stderr);
+        }
+        out.flush();
+        let case = CustomCase {
+            name: format!("stress-{}", seed),
+            input,
+            output: expected,
+        };
+        let path = case.save(&problem_id)?;
+        println!("Failing input saved as custom case `{}` ({}).", case.name, path.display());
+        Err("")?
+    }
+    println!();
+    println!("All {} iterations passed.", iterations);
+    Ok(())
+}
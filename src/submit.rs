@@ -1,5 +1,9 @@
-use crate::datastore::Cookies;
-use crate::UA;
+use crate::compare::DiffStyle;
+use crate::datastore::{Cookies, Credentials, History, Settings, Submission};
+use crate::{bojenv, bundle, language, minify, test};
+use crate::optparse::{get_language_id, Build, Diagnostics, LanguageType, Submit, Test};
+use crate::report::Format;
+use crate::{Result, UA};
 use crossterm::{
     cursor,
     event::{self, Event},
@@ -7,18 +11,161 @@ use crossterm::{
     terminal::{self, ClearType},
 };
 use reqwest::{blocking::Client, cookie::Jar, Url};
-use scraper::{Html, Selector};
+use scraper::{CaseSensitivity, ElementRef, Html, Selector};
+use sha2::{Digest, Sha256};
+use std::fs;
 use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
+// BOJ rejects source files longer than this, in bytes
+pub const SOURCE_LIMIT: usize = 65536;
+
+// Returns the size of the source, or an error if BOJ would reject it
+pub fn check_size(source: &str) -> Result<usize> {
+    let size = source.len();
+    if size > SOURCE_LIMIT {
+        Err(format!(
+            "Error: the source is {} bytes, over BOJ's limit of {} bytes.",
+            size, SOURCE_LIMIT
+        ))?
+    }
+    Ok(size)
+}
+
+// The file at the given path, or else src/main.rs or src/bin/main.rs
+pub fn solution_path(path: Option<String>) -> Option<PathBuf> {
+    match path {
+        Some(path) => Some(PathBuf::from(path)),
+        None => ["src/main.rs", "src/bin/main.rs"]
+            .into_iter()
+            .map(PathBuf::from)
+            .find(|file| file.exists()),
+    }
+}
+
+pub fn submit(opts: Submit) -> Result<()> {
+    let Submit {
+        problem_id,
+        path,
+        language,
+        code_open,
+        no_bundle,
+        test_first,
+    } = opts;
+    let test_first = match test_first {
+        Some(test_first) => test_first,
+        None => Settings::load()?.test_first,
+    };
+    // Without --lang, a source file with a language profile is submitted in that language
+    let language = match (language, &path) {
+        (None, Some(path)) => match language::find_profile(Path::new(path), None)? {
+            Some(profile) => profile.id,
+            None => get_language_id(None),
+        },
+        (language, _) => get_language_id(language),
+    };
+    let credentials = Credentials::load();
+    let Some(cookies) = &credentials.cookies else {
+        Err("Use `cargo-boj login` first to log in.")?
+    };
+    let source_path = solution_path(path.clone());
+    let source = source_path
+        .as_ref()
+        .and_then(|path| fs::read_to_string(path).ok());
+    let Some(mut source) = source else {
+        if let Some(path) = &path {
+            Err(format!("{} not found.", path))?
+        } else {
+            Err("Neither src/main.rs nor src/bin/main.rs not found. Try running again at the crate root.")?
+        }
+    };
+    // BOJ takes a single file, so Rust modules and local library crates are bundled into it
+    if bojenv::is_rust(language) && !no_bundle {
+        source = minify::minify(&bundle::bundle(source_path.as_ref().unwrap())?)?;
+    }
+    let size = check_size(&source)?;
+    println!("Source size: {} bytes (BOJ's limit is {} bytes)", size, SOURCE_LIMIT);
+    if test_first {
+        let extension = source_path
+            .as_ref()
+            .and_then(|path| path.extension())
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("rs");
+        if let Err(e) = test_source(&problem_id, &source, extension, language) {
+            let message = e.to_string();
+            if !message.is_empty() {
+                eprintln!("{}", message);
+            }
+            Err("Error: the solution did not pass the tests, so it was not submitted.")?
+        }
+    }
+    let verdict = submit_solution(
+        cookies,
+        &problem_id,
+        &source,
+        language,
+        code_open.map(|x| x.to_string()),
+    )?;
+    // The exit status tells scripts whether the solution was accepted
+    match verdict {
+        Some("Accepted") => Ok(()),
+        // The verdict is already shown
+        Some(_) => Err("")?,
+        None => Err("Error: exited before the judging finished. Check the status page for the verdict.")?,
+    }
+}
+
+// Tests the source exactly as it is submitted: Rust is compiled as BOJ does, other languages with their profile.
+fn test_source(problem_id: &str, source: &str, extension: &str, language: usize) -> Result<()> {
+    let mut dir = language::build_dir();
+    dir.push("submit");
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("Main.{}", extension));
+    fs::write(&path, source)?;
+    let result = test::test(Test {
+        problem_id: problem_id.to_string(),
+        bin_or_cmd: None,
+        // Special Judge outputs are confirmed by the user as with `test --spj-prompt`
+        spj_prompt: true,
+        refresh: false,
+        time_factor: 1.0,
+        memory_rlimit: false,
+        checker: None,
+        compare: None,
+        diff_style: DiffStyle::Unified,
+        format: Format::Text,
+        keep_going: Some(false),
+        watch: false,
+        path: Some(path.display().to_string()),
+        build: Build {
+            profile: None,
+            debug: false,
+            checks: false,
+            features: None,
+            diagnostics: Diagnostics::Full,
+        },
+        language: Some(LanguageType::Id(language)),
+        boj_env: bojenv::is_rust(language),
+        boj_toolchain: false,
+        // The source is already bundled unless --no-bundle is given
+        no_bundle: true,
+        interactor: None,
+        two_steps: None,
+        jobs: 1,
+    });
+    let _ = fs::remove_dir_all(&dir);
+    result
+}
+
 pub fn submit_solution(
     cookies: &Cookies,
     problem_id: &str,
     source: &str,
     language: usize,
     code_open: Option<String>,
-) {
+) -> Result<Option<&'static str>> {
     let Cookies {
         bojautologin,
         onlinejudge,
@@ -48,8 +195,7 @@ pub fn submit_solution(
     let mut output = String::new();
     res.read_to_string(&mut output).unwrap();
     if res.url().as_str().contains("login") {
-        println!("Submit page access failed. Please log in.");
-        return;
+        Err("Submit page access failed. Please log in.")?
     }
 
     let html = Html::parse_document(&output);
@@ -92,47 +238,162 @@ pub fn submit_solution(
     println!("    Language ID: {}", language);
     println!("    Status page: {}", url);
 
+    let mut submission = Submission {
+        problem_id: problem_id.to_string(),
+        solution_id: sol_id_no.to_string(),
+        language,
+        code_open,
+        source_hash: Sha256::digest(source.as_bytes())
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect(),
+        timestamp: chrono::Utc::now(),
+        verdict: None,
+        time: None,
+        memory: None,
+    };
+    // The submission is already made, so failing to record it is not an error
+    if let Err(e) = History::save(&submission) {
+        eprintln!("Warning: could not record the submission: {}", e);
+    }
+
     println!("Press any key to exit.");
-    submit_loop(&client, url, sol_id);
+    let Some(status) = submit_loop(&client, url, sol_id) else {
+        return Ok(None);
+    };
+    status.print_details();
+    submission.verdict = Some(status.verdict.to_string());
+    submission.time = status.time;
+    submission.memory = status.memory;
+    if let Err(e) = History::save(&submission) {
+        eprintln!("Warning: could not record the verdict: {}", e);
+    }
+    Ok(Some(status.verdict))
 }
 
-fn submit_loop(client: &Client, url: &str, sol_id: &str) {
+// A submission's row on the status page
+struct Status {
+    verdict: &'static str,
+    // Percentage of the test data judged so far, while judging
+    progress: Option<f64>,
+    // In kilobytes
+    memory: Option<u64>,
+    // In milliseconds
+    time: Option<u64>,
+    // In bytes
+    code_length: Option<u64>,
+    // Points of score and subtask problems
+    score: Option<f64>,
+}
+
+// Parses the number right before the suffix, e.g. "채점 중 (45%)" with "%" -> 45
+fn number_before(text: &str, suffix: &str) -> Option<f64> {
+    let end = text.find(suffix)?;
+    let text = text[..end].trim_end();
+    let start = text
+        .rfind(|c: char| !c.is_ascii_digit() && c != '.')
+        .map_or(0, |i| i + 1);
+    text[start..].parse().ok()
+}
+
+// Parses the number in a cell, which is empty until it is known (memory and time are only shown when accepted)
+fn cell_number(cell: Option<ElementRef>) -> Option<u64> {
+    cell?.text().collect::<String>().trim().parse().ok()
+}
+
+impl Status {
+    fn parse(html: &Html, sol_id: &str) -> Self {
+        let result_selector =
+            Selector::parse(&format!("#{} td.result span.result-text", sol_id)).unwrap();
+        let result_el = html.select(&result_selector).next().unwrap();
+        let classes = result_el.value().classes().collect::<Vec<_>>();
+        let verdict = classify_class(&classes);
+        let result_text = result_el.text().collect::<String>();
+        let cell_selector = Selector::parse(&format!("#{} td", sol_id)).unwrap();
+        let cells = html.select(&cell_selector).collect::<Vec<_>>();
+        let cell = |class: &str| {
+            cells
+                .iter()
+                .position(|el| el.value().has_class(class, CaseSensitivity::CaseSensitive))
+        };
+        // The cells go: ..., memory, time, language, code length, submitted time
+        let time = cell("time");
+        Status {
+            verdict,
+            progress: (verdict == "Judging")
+                .then(|| number_before(&result_text, "%"))
+                .flatten(),
+            memory: cell_number(cell("memory").map(|i| cells[i])),
+            time: cell_number(time.map(|i| cells[i])),
+            code_length: cell_number(time.and_then(|i| cells.get(i + 2).copied())),
+            score: judge_finished(verdict)
+                .then(|| {
+                    number_before(&result_text, "점")
+                        .or_else(|| number_before(&result_text, "point"))
+                })
+                .flatten(),
+        }
+    }
+
+    // e.g. "Judging (45%)"
+    fn summary(&self) -> String {
+        match self.progress {
+            Some(progress) => format!("{} ({}%)", self.verdict, progress),
+            None => self.verdict.to_string(),
+        }
+    }
+
+    fn print_details(&self) {
+        if let Some(score) = self.score {
+            println!("    Score: {}", score);
+        }
+        if let Some(memory) = self.memory {
+            println!("    Memory: {} KB", memory);
+        }
+        if let Some(time) = self.time {
+            println!("    Time: {} ms", time);
+        }
+        if let Some(code_length) = self.code_length {
+            println!("    Code length: {} B", code_length);
+        }
+    }
+}
+
+// Shows the status until the judging finishes, or the user presses a key to exit (then None is returned)
+fn submit_loop(client: &Client, url: &str, sol_id: &str) -> Option<Status> {
     let mut stdout = std::io::stdout();
     terminal::enable_raw_mode().unwrap();
     execute!(stdout, cursor::SavePosition).unwrap();
-    'outer: loop {
+    let status = 'outer: loop {
         let mut res = client.get(url).send().unwrap();
         let mut output = String::new();
         res.read_to_string(&mut output).unwrap();
         let html = Html::parse_document(&output);
-        let sol_selector =
-            Selector::parse(&format!("#{} td.result span.result-text", sol_id)).unwrap();
-        let sol_el = html.select(&sol_selector).next().unwrap();
-        let classes = sol_el.value().classes().collect::<Vec<_>>();
-        let verdict = classify_class(&classes);
+        let status = Status::parse(&html, sol_id);
         execute!(
             stdout,
             terminal::Clear(ClearType::CurrentLine),
             cursor::RestorePosition
         )
         .unwrap();
-        print!("Current status: {} ", verdict);
+        print!("Current status: {} ", status.summary());
         stdout.flush().unwrap();
-        if judge_finished(verdict) {
-            break;
+        if judge_finished(status.verdict) {
+            break Some(status);
         }
 
         let now = Instant::now();
         while now.elapsed() < Duration::from_secs(1) {
             if event::poll(Duration::from_secs(0)).unwrap() {
                 if let Event::Key(_) = event::read().unwrap() {
-                    break 'outer;
+                    break 'outer None;
                 };
             }
         }
-    }
+    };
     terminal::disable_raw_mode().unwrap();
     println!();
+    status
 }
 
 fn classify_class(classes: &[&str]) -> &'static str {
@@ -1,152 +1,1154 @@
-use std::io::Write;
-use std::path::PathBuf;
-use std::process::{Child, Command, Stdio};
-use std::time::Duration;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use console::Style;
+use once_cell::sync::Lazy;
+use serde::Serialize;
 use crossterm::event::{Event, KeyCode};
 use crossterm::{terminal, event};
-use similar::{ChangeTag, TextDiff};
 
-use crate::datastore::ProblemData;
-use crate::optparse::BinOrCmd;
+use crate::checker::Checker;
+use crate::compare::{Compare, DiffStyle};
+use crate::datastore::{CustomCase, LanguageProfiles, ProblemData, Settings};
+use crate::interactive::run_interactive;
+use crate::language;
+use crate::metadata::CurrentPackage;
+use crate::{bojenv, bundle, minify};
+use crate::optparse::{
+    get_language_id, BinOrCmd, Build, Diagnostics, LanguageType, Test, TwoSteps,
+};
+use crate::report::{self, CaseRecord, Format};
+use crate::watch;
 use crate::Result;
 
-fn precompile_bin(bin: &str) -> Result<()> {
+// Prints the compiler messages from cargo's JSON output, or only the errors.
+// Failures that are not compiler messages, such as a broken Cargo.toml, are only in stderr.
+fn print_build_messages(messages: &[serde_json::Value], stderr: &str, diagnostics: Diagnostics) {
+    let mut printed = false;
+    for message in messages {
+        if message["reason"] != "compiler-message" {
+            continue;
+        }
+        let message = &message["message"];
+        if diagnostics == Diagnostics::Errors && message["level"] != "error" {
+            continue;
+        }
+        if let Some(rendered) = message["rendered"].as_str() {
+            eprint!("{}", rendered);
+            printed = true;
+        }
+    }
+    if !printed {
+        eprint!("{}", stderr);
+    }
+}
+
+// Builds the bin and returns the path of its executable, as reported by cargo.
+pub fn precompile_bin(bin: &str, build: &Build) -> Result<PathBuf> {
+    let default_profile = if build.debug { "dev" } else { "release" };
+    let profile = build.profile.as_deref().unwrap_or(default_profile);
     let mut command = Command::new("cargo");
-    command.args(["build", "--bin", bin, "--release"]);
-    let exitstatus = command
-        .stdin(Stdio::null())
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .spawn()
-        .unwrap()
-        .wait()
-        .unwrap();
-    if !exitstatus.success() {
-        Err(format!("Error: `cargo build --bin {} --release` failed.", bin))?
+    command.args(["build", "--bin", bin, "--profile", profile]);
+    if let Some(features) = &build.features {
+        command.args(["--features", features]);
     }
-    Ok(())
+    if build.checks {
+        let mut rustflags = std::env::var("RUSTFLAGS").unwrap_or_default();
+        rustflags.push_str(" -C overflow-checks=on -C debug-assertions=on");
+        command.env("RUSTFLAGS", rustflags.trim());
+    }
+    let message_format = match (build.diagnostics, console::colors_enabled_stderr()) {
+        (Diagnostics::Full, true) => "json-diagnostic-rendered-ansi",
+        (Diagnostics::Full, false) => "json",
+        (Diagnostics::Errors, true) => "json-diagnostic-short,json-diagnostic-rendered-ansi",
+        (Diagnostics::Errors, false) => "json-diagnostic-short",
+    };
+    command.arg(format!("--message-format={}", message_format));
+    let output = command.stdin(Stdio::null()).output()?;
+    let messages = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .collect::<Vec<_>>();
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        print_build_messages(&messages, &stderr, build.diagnostics);
+        Err(format!("Error: `cargo build --bin {} --profile {}` failed.", bin, profile))?
+    }
+    // The executable's location depends on the target directory and the profile,
+    // which may be configured in many ways, so take it from cargo
+    let executable = messages.iter().find_map(|message| {
+        let target = &message["target"];
+        let is_bin = target["kind"]
+            .as_array()
+            .is_some_and(|kinds| kinds.iter().any(|kind| kind == "bin"));
+        (message["reason"] == "compiler-artifact" && is_bin && target["name"] == bin)
+            .then(|| message["executable"].as_str())
+            .flatten()
+    });
+    match executable {
+        Some(executable) => Ok(PathBuf::from(executable)),
+        None => Err(format!("Error: cargo did not report the executable of bin `{}`.", bin))?,
+    }
+}
+
+// A solution or a helper program, ready to run
+pub enum Program {
+    // `backtrace` makes panics print a backtrace
+    Exe { path: PathBuf, backtrace: bool },
+    Cmd(String),
 }
 
-fn spawn_bin_or_cmd(bin_or_cmd: &BinOrCmd) -> Child {
-    let mut command = match bin_or_cmd {
-        BinOrCmd::Bin(bin) => {
-            let mut path = "target/release".parse::<PathBuf>().unwrap();
-            path.push(bin);
-            path.set_extension(std::env::consts::EXE_EXTENSION);
-            Command::new(&path)
-        }
-        BinOrCmd::Cmd(cmd) => {
-            if cfg!(target_os = "windows") {
-                let mut command = Command::new("cmd");
-                command.arg("/C").arg(cmd);
-                command
-            } else {
-                let mut command = Command::new("sh");
-                command.arg("-c").arg(cmd);
-                command
+// Builds the program first if it is a bin in the current crate.
+pub fn build_program(bin_or_cmd: &BinOrCmd, build: &Build) -> Result<Program> {
+    match bin_or_cmd {
+        BinOrCmd::Bin(bin) => Ok(Program::Exe {
+            path: precompile_bin(bin, build)?,
+            backtrace: build.debug || build.checks,
+        }),
+        BinOrCmd::Cmd(cmd) => Ok(Program::Cmd(cmd.clone())),
+    }
+}
+
+// Resource limits applied to each test run. Memory is in kilobytes, as reported by BOJ.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub time: Option<Duration>,
+    pub memory: Option<u64>,
+    // If set, the memory limit is also enforced on the process with an rlimit
+    pub enforce_memory: bool,
+    // Number of cases running in parallel, which slow each other down
+    pub jobs: usize,
+}
+
+impl Limits {
+    // The wall-clock time after which the program is killed. Cases running in parallel get more,
+    // since they are judged on CPU time where it is known, and are killed once that is over the limit
+    pub fn wall_limit(&self) -> Option<Duration> {
+        self.time.map(|time| time * self.jobs.max(1) as u32)
+    }
+
+    // Whether the sampled CPU time of a case running in parallel is over the time limit
+    pub fn cpu_exceeded(&self, cpu: &CpuTime) -> bool {
+        self.jobs > 1 && self.time.is_some_and(|time| cpu.used > time)
+    }
+
+    // The time to report for a finished program, and whether it is over the time limit.
+    // That is the wall-clock time, or the CPU time for cases running in parallel (Linux only).
+    pub fn time_used(&self, wall: Duration, cpu: Option<Duration>) -> (Duration, bool) {
+        let used = match cpu {
+            Some(cpu) if self.jobs > 1 => cpu,
+            _ => wall,
+        };
+        (used, self.time.is_some_and(|time| used > time))
+    }
+
+    // The time to report for a killed program. The wall-clock time of cases running in parallel
+    // is not what they are judged on, so their CPU time is reported if it is exact, or else the limit.
+    pub fn time_killed(&self, wall: Duration, cpu: Option<Duration>) -> Duration {
+        match self.time {
+            Some(time) if self.jobs > 1 => cpu.map_or(time, |cpu| cpu.max(time)),
+            _ => wall,
+        }
+    }
+}
+
+// Builds a command running `cmd` through the platform's shell.
+pub fn shell_command(cmd: &str) -> Command {
+    if cfg!(target_os = "windows") {
+        let mut command = Command::new("cmd");
+        command.arg("/C").arg(cmd);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(cmd);
+        command
+    }
+}
+
+pub fn spawn_program(program: &Program, args: &[String], limits: &Limits) -> Child {
+    let mut command = match program {
+        Program::Exe { path, backtrace } => {
+            let mut command = Command::new(path);
+            command.args(args);
+            if *backtrace {
+                command.env("RUST_BACKTRACE", "1");
             }
+            command
+        }
+        Program::Cmd(cmd) => {
+            let args = args.iter().map(|arg| format!(" \"{}\"", arg)).collect::<String>();
+            shell_command(&format!("{}{}", cmd, args))
         }
     };
+    if let (true, Some(memory)) = (limits.enforce_memory, limits.memory) {
+        set_memory_rlimit(&mut command, memory);
+    }
     command
-    .stdin(Stdio::piped())
-    .stdout(Stdio::piped())
-    .spawn()
-    .unwrap()
-}
-
-fn run_test_case(bin_or_cmd: &BinOrCmd, spj: bool, input: &str, output: &str) -> Result<()> {
-    let mut handle = spawn_bin_or_cmd(bin_or_cmd);
-    let now = std::time::Instant::now();
-    let stdin = handle.stdin.as_mut().unwrap();
-    write!(stdin, "{}", input).unwrap();
-    let stdout = handle.wait_with_output().unwrap();
-    let elapsed = now.elapsed().as_micros();
-    let result = String::from_utf8_lossy(&stdout.stdout).to_string();
-
-    let output = output
-        .trim_end()
-        .lines()
-        .map(|l| l.trim_end())
-        .collect::<Vec<_>>()
-        .join("\n");
-    let result = result
-        .trim_end()
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    spawn_group(&mut command).unwrap()
+}
+
+#[cfg(target_os = "linux")]
+fn set_memory_rlimit(command: &mut Command, memory: u64) {
+    use std::os::unix::process::CommandExt;
+    let bytes = memory * 1024;
+    let limit = libc::rlimit {
+        rlim_cur: bytes,
+        rlim_max: bytes,
+    };
+    // SAFETY: setrlimit is async-signal-safe and the closure does not allocate
+    unsafe {
+        command.pre_exec(move || {
+            if libc::setrlimit(libc::RLIMIT_AS, &limit) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_memory_rlimit(_command: &mut Command, _memory: u64) {}
+
+// Process groups of the programs running now. Being outside the terminal's process group,
+// they don't get the Ctrl-C cargo-boj gets, so they are killed when it is interrupted.
+#[cfg(unix)]
+static RUNNING_GROUPS: Lazy<Mutex<HashSet<u32>>> = Lazy::new(|| {
+    use signal_hook::consts::{SIGHUP, SIGINT, SIGTERM};
+    let mut signals = signal_hook::iterator::Signals::new([SIGINT, SIGTERM, SIGHUP]).unwrap();
+    thread::spawn(move || {
+        if let Some(signal) = signals.forever().next() {
+            for &group in RUNNING_GROUPS.lock().unwrap().iter() {
+                // SAFETY: killpg only sends a signal
+                unsafe { libc::killpg(group as libc::pid_t, libc::SIGKILL) };
+            }
+            std::process::exit(128 + signal);
+        }
+    });
+    Mutex::new(HashSet::new())
+});
+
+// Spawns the command in a process group of its own, so that `kill_program` also kills the processes it starts,
+// such as the program a --cmd shell runs. `reaped` must be called once it has been waited for.
+#[cfg(unix)]
+pub fn spawn_group(command: &mut Command) -> std::io::Result<Child> {
+    use std::os::unix::process::CommandExt;
+    let mut groups = RUNNING_GROUPS.lock().unwrap();
+    let child = command.process_group(0).spawn()?;
+    groups.insert(child.id());
+    Ok(child)
+}
+
+#[cfg(not(unix))]
+pub fn spawn_group(command: &mut Command) -> std::io::Result<Child> {
+    command.spawn()
+}
+
+#[cfg(unix)]
+pub fn reaped(handle: &Child) {
+    RUNNING_GROUPS.lock().unwrap().remove(&handle.id());
+}
+
+#[cfg(not(unix))]
+pub fn reaped(_handle: &Child) {}
+
+// Kills the program started with `spawn_group`, along with the processes it started.
+#[cfg(unix)]
+pub fn kill_program(handle: &mut Child) -> std::io::Result<()> {
+    // SAFETY: killpg only sends a signal
+    if unsafe { libc::killpg(handle.id() as libc::pid_t, libc::SIGKILL) } != 0 {
+        let error = std::io::Error::last_os_error();
+        // The whole group has already exited
+        if error.raw_os_error() != Some(libc::ESRCH) {
+            return Err(error);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn kill_program(handle: &mut Child) -> std::io::Result<()> {
+    handle.kill()
+}
+
+// Time to wait for the I/O threads to drain the pipes after the processes exit
+pub const IO_GRACE: Duration = Duration::from_millis(100);
+
+pub fn join_within<T>(handle: thread::JoinHandle<T>, timeout: Duration) -> Option<T> {
+    let now = Instant::now();
+    while !handle.is_finished() {
+        if now.elapsed() > timeout {
+            return None;
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    Some(handle.join().unwrap())
+}
+
+// The peak resident memory of a running program in kilobytes, sampled while it runs (Linux only).
+// ru_maxrss from wait4 alone can't be trusted: the child carries cargo-boj's own peak into it when it execs.
+#[derive(Default)]
+pub struct PeakMemory(Option<u64>);
+
+// A running program is polled often at first, so that the memory of short runs is sampled too
+pub const POLL_INTERVAL_MIN: Duration = Duration::from_micros(50);
+pub const POLL_INTERVAL_MAX: Duration = Duration::from_millis(1);
+
+// VmHWM of the process, which is gone once it exits
+#[cfg(target_os = "linux")]
+fn process_peak_memory(pid: &str) -> Option<u64> {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).ok()?;
+    status
         .lines()
-        .map(|l| l.trim_end())
-        .collect::<Vec<_>>()
-        .join("\n");
-    let diff = TextDiff::from_lines(&result, &output);
-    let styles = if spj {
-        (Style::new(), Style::new(), Style::new())
+        .find_map(|line| line.strip_prefix("VmHWM:"))
+        .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+}
+
+#[cfg(target_os = "linux")]
+static OWN_EXE: Lazy<Option<PathBuf>> = Lazy::new(|| std::fs::read_link("/proc/self/exe").ok());
+
+// VmHWM of a spawned process, once it has exec'd the program.
+// Before that, it still runs cargo-boj's image and its VmHWM is cargo-boj's own.
+#[cfg(target_os = "linux")]
+fn program_peak_memory(pid: u32) -> Option<u64> {
+    // Checked before the status is read, as exec can't be undone
+    let exe = std::fs::read_link(format!("/proc/{}/exe", pid)).ok()?;
+    if OWN_EXE.as_ref().is_none_or(|own| &exe == own) {
+        return None;
+    }
+    process_peak_memory(&pid.to_string())
+}
+
+#[cfg(target_os = "linux")]
+fn child_pids(pid: u32) -> Vec<u32> {
+    std::fs::read_dir(format!("/proc/{}/task", pid))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|task| std::fs::read_to_string(task.path().join("children")).ok())
+        .flat_map(|children| {
+            children
+                .split_whitespace()
+                .filter_map(|child| child.parse().ok())
+                .collect::<Vec<u32>>()
+        })
+        .collect()
+}
+
+// The largest VmHWM among the process and its descendants, e.g. the program run by a --cmd shell
+#[cfg(target_os = "linux")]
+fn tree_peak_memory(pid: u32) -> Option<u64> {
+    let own = program_peak_memory(pid);
+    let children = child_pids(pid).into_iter().filter_map(tree_peak_memory).max();
+    own.max(children)
+}
+
+// The CPU time a running program has used, sampled while it runs (Linux only)
+#[derive(Default)]
+pub struct CpuTime {
+    used: Duration,
+    // Whether the program started other processes. wait4 misses their CPU time if they are killed.
+    pub forked: bool,
+}
+
+impl CpuTime {
+    #[cfg(target_os = "linux")]
+    pub fn sample(&mut self, pid: u32) {
+        // SAFETY: sysconf only reads a configuration value
+        let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as f64;
+        let children = child_pids(pid);
+        self.forked |= !children.is_empty();
+        // utime and stime of the process, and cutime and cstime of the children it waited for
+        let own = std::fs::read_to_string(format!("/proc/{}/stat", pid))
+            .ok()
+            .and_then(|stat| {
+                // The fields start after the command name, which may contain spaces
+                let fields = stat.rsplit_once(')')?.1.split_whitespace().collect::<Vec<_>>();
+                fields[11..15]
+                    .iter()
+                    .map(|field| field.parse::<u64>().ok())
+                    .sum::<Option<u64>>()
+            })
+            .map_or(Duration::ZERO, |used| Duration::from_secs_f64(used as f64 / ticks));
+        let mut used = own;
+        for child in children {
+            let mut child_time = CpuTime::default();
+            child_time.sample(child);
+            used += child_time.used;
+        }
+        self.used = self.used.max(used);
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub fn sample(&mut self, _pid: u32) {}
+}
+
+// How a program finished
+pub struct Finished {
+    pub status: ExitStatus,
+    // Peak resident memory in kilobytes (Linux only)
+    pub memory: Option<u64>,
+    // User and system CPU time, including the processes it started and waited for (Linux only)
+    pub cpu: Option<Duration>,
+}
+
+// Waits for the program to finish (or only checks if `block` is false).
+// On Linux, also returns the peak resident memory of the program in kilobytes, as sampled by the calls so far,
+// and its CPU time.
+#[cfg(target_os = "linux")]
+pub fn wait_with_memory(handle: &mut Child, peak: &mut PeakMemory, block: bool) -> Option<Finished> {
+    use std::os::unix::process::ExitStatusExt;
+    if !block {
+        peak.0 = peak.0.max(tree_peak_memory(handle.id()));
+    }
+    let mut status = 0;
+    // SAFETY: rusage is a plain C struct, for which all-zero bytes is a valid value
+    let mut usage = unsafe { std::mem::zeroed::<libc::rusage>() };
+    let flags = if block { 0 } else { libc::WNOHANG };
+    // SAFETY: the pointers are valid for the duration of the call
+    let pid = unsafe { libc::wait4(handle.id() as libc::pid_t, &mut status, flags, &mut usage) };
+    if pid == 0 {
+        return None;
+    }
+    if pid < 0 {
+        panic!("wait4 failed: {}", std::io::Error::last_os_error());
+    }
+    reaped(handle);
+    // ru_maxrss is at least cargo-boj's peak when the child exec'd, which is at most its peak now.
+    // Above that, it is the program's own peak, which is exact even if the program exits between samples.
+    // Otherwise only the samples taken after the exec are used, and the memory is unknown without them.
+    let maxrss = usage.ru_maxrss as u64;
+    let memory = if process_peak_memory("self").is_some_and(|own| maxrss > own) {
+        Some(maxrss)
     } else {
-        (Style::new().red(), Style::new().green(), Style::new())
+        peak.0
     };
-    let mut failed = false;
-    for op in diff.ops() {
-        for change in diff.iter_changes(op) {
-            let (sign, style) = match change.tag() {
-                ChangeTag::Delete => {
-                    failed = true;
-                    ("-", &styles.0)
-                }
-                ChangeTag::Insert => {
-                    failed = true;
-                    ("+", &styles.1)
+    let timeval = |t: libc::timeval| Duration::new(t.tv_sec as u64, t.tv_usec as u32 * 1000);
+    Some(Finished {
+        status: ExitStatus::from_raw(status),
+        memory,
+        cpu: Some(timeval(usage.ru_utime) + timeval(usage.ru_stime)),
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn wait_with_memory(handle: &mut Child, _peak: &mut PeakMemory, block: bool) -> Option<Finished> {
+    let status = if block {
+        Some(handle.wait().unwrap())
+    } else {
+        handle.try_wait().unwrap()
+    };
+    if status.is_some() {
+        reaped(handle);
+    }
+    status.map(|status| Finished {
+        status,
+        memory: None,
+        cpu: None,
+    })
+}
+
+enum Stream {
+    Stdout,
+    Stderr,
+}
+
+// What a test case prints, buffered so that cases run in parallel are shown in order
+#[derive(Default)]
+pub struct Output {
+    chunks: Vec<(Stream, String)>,
+}
+
+impl Output {
+    pub fn print(&mut self, s: impl fmt::Display) {
+        self.chunks.push((Stream::Stdout, s.to_string()));
+    }
+
+    pub fn println(&mut self, s: impl fmt::Display) {
+        self.chunks.push((Stream::Stdout, format!("{}\n", s)));
+    }
+
+    pub fn eprintln(&mut self, s: impl fmt::Display) {
+        self.chunks.push((Stream::Stderr, format!("{}\n", s)));
+    }
+
+    pub fn flush(self) {
+        for (stream, chunk) in self.chunks {
+            match stream {
+                Stream::Stdout => {
+                    print!("{}", chunk);
+                    std::io::stdout().flush().unwrap();
                 }
-                ChangeTag::Equal => (" ", &styles.2),
-            };
-            print!("{}{}", style.apply_to(sign), style.apply_to(change));
+                Stream::Stderr => eprint!("{}", chunk),
+            }
         }
     }
-    if !spj && failed {
-        std::io::stdout().flush()?;
-        eprintln!("{} on input:", Style::new().red().apply_to("Test failed"));
-        eprintln!("{}", input);
-        Err("")?
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Accepted,
+    WrongAnswer,
+    TimeLimitExceeded,
+    MemoryLimitExceeded,
+    RuntimeError,
+    // The output differs from the expected output on a Special Judge problem
+    Unknown,
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verdict::Accepted => "Accepted".fmt(f),
+            Verdict::WrongAnswer => "Wrong Answer".fmt(f),
+            Verdict::TimeLimitExceeded => "Time Limit Exceeded".fmt(f),
+            Verdict::MemoryLimitExceeded => "Memory Limit Exceeded".fmt(f),
+            Verdict::RuntimeError => "Runtime Error".fmt(f),
+            Verdict::Unknown => "Unknown".fmt(f),
+        }
     }
-    println!("Elapsed: {}.{:06}", elapsed / 1000000, elapsed % 1000000);
+}
+
+// The result of running a single test case
+pub struct Outcome {
+    pub verdict: Verdict,
+    pub elapsed: Duration,
+    pub memory: Option<u64>,
+    // The program's output, or the transcript for interactive problems
+    pub stdout: String,
+}
+
+// Where a test case comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    Sample,
+    Custom,
+    // The single run with an empty input for interactive problems without cases
+    Interactive,
+}
+
+pub struct TestCase {
+    pub source: Source,
+    // The sample number or the custom case name
+    pub id: String,
+    pub input: String,
+    pub output: String,
+}
+
+impl TestCase {
+    pub fn label(&self) -> String {
+        match self.source {
+            Source::Sample => format!("Sample {}", self.id),
+            Source::Custom => format!("Custom {}", self.id),
+            Source::Interactive => "Interactive".to_string(),
+        }
+    }
+}
+
+pub struct Execution {
+    pub stdout: String,
+    pub stderr: String,
+    pub elapsed: Duration,
+    pub memory: Option<u64>,
+    pub status: ExitStatus,
+    pub timed_out: bool,
+}
+
+pub fn format_elapsed(elapsed: Duration) -> String {
+    let elapsed = elapsed.as_micros();
+    format!("{}.{:06}", elapsed / 1000000, elapsed % 1000000)
+}
+
+pub fn spawn_reader(mut pipe: impl Read + Send + 'static) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = vec![];
+        pipe.read_to_end(&mut buf).unwrap();
+        buf
+    })
+}
+
+// Writes the Rust source bundled and minified as `submit` sends it, and returns the path of the file.
+fn bundled_source(source: &Path) -> Result<PathBuf> {
+    let bundled = minify::minify(&bundle::bundle(source)?)?;
+    let mut dir = language::build_dir();
+    dir.push("bundle");
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("Main.rs");
+    std::fs::write(&path, bundled)?;
+    Ok(path)
+}
+
+// Runs the program on the given input, killing it once it runs longer than the time limit.
+pub fn execute(program: &Program, args: &[String], input: &str, limits: &Limits) -> Execution {
+    let mut handle = spawn_program(program, args, limits);
+    let now = Instant::now();
+    // Feed stdin and drain stdout/stderr on separate threads so that neither side blocks on a full pipe
+    let mut stdin = handle.stdin.take().unwrap();
+    let input = input.to_owned();
+    let writer = thread::spawn(move || {
+        // The program may exit without reading all of its input
+        let _ = write!(stdin, "{}", input);
+    });
+    let stdout_reader = spawn_reader(handle.stdout.take().unwrap());
+    let stderr_reader = spawn_reader(handle.stderr.take().unwrap());
+    let mut peak = PeakMemory::default();
+    let mut cpu_time = CpuTime::default();
+    let mut interval = POLL_INTERVAL_MIN;
+    let (finished, killed) = loop {
+        if let Some(finished) = wait_with_memory(&mut handle, &mut peak, false) {
+            break (finished, false);
+        }
+        if limits.jobs > 1 {
+            cpu_time.sample(handle.id());
+        }
+        let over_wall = limits.wall_limit().is_some_and(|limit| now.elapsed() > limit);
+        if over_wall || limits.cpu_exceeded(&cpu_time) {
+            kill_program(&mut handle).unwrap();
+            break (wait_with_memory(&mut handle, &mut peak, true).unwrap(), true);
+        }
+        thread::sleep(interval);
+        interval = (interval * 2).min(POLL_INTERVAL_MAX);
+    };
+    let Finished {
+        status,
+        memory,
+        cpu,
+    } = finished;
+    let (elapsed, timed_out) = if killed {
+        // The CPU time of a killed program misses the processes it started, which were never waited for
+        let exact = matches!(program, Program::Exe { .. }) && !cpu_time.forked;
+        (limits.time_killed(now.elapsed(), cpu.filter(|_| exact)), true)
+    } else {
+        limits.time_used(now.elapsed(), cpu)
+    };
+    if killed {
+        // The whole process group is killed, so the pipes close; the grace only guards against
+        // a process that left the group. The output is discarded anyway
+        join_within(writer, IO_GRACE);
+        join_within(stdout_reader, IO_GRACE);
+        join_within(stderr_reader, IO_GRACE);
+        return Execution {
+            stdout: String::new(),
+            stderr: String::new(),
+            elapsed,
+            memory,
+            status,
+            timed_out,
+        };
+    }
+    writer.join().unwrap();
+    let stdout = stdout_reader.join().unwrap();
+    let stderr = stderr_reader.join().unwrap();
+    Execution {
+        stdout: String::from_utf8_lossy(&stdout).to_string(),
+        stderr: String::from_utf8_lossy(&stderr).to_string(),
+        elapsed,
+        memory,
+        status,
+        timed_out,
+    }
+}
+
+// Returns the arguments and the input for the given step (1 or 2) of a two-step problem.
+fn two_steps_invocation(two_steps: &TwoSteps, step: usize, input: &str) -> (Vec<String>, String) {
+    match two_steps {
+        TwoSteps::Plain => (vec![], input.to_string()),
+        TwoSteps::Arg => (vec![step.to_string()], input.to_string()),
+        TwoSteps::Line(first, second) => {
+            let line = if step == 1 { first } else { second };
+            (vec![], format!("{}\n{}", line, input))
+        }
+    }
+}
+
+// Runs both steps of a two-step problem, feeding the first step's output to the second step.
+fn execute_two_steps(
+    program: &Program,
+    two_steps: &TwoSteps,
+    input: &str,
+    limits: &Limits,
+    out: &mut Output,
+) -> Execution {
+    let (args, first_input) = two_steps_invocation(two_steps, 1, input);
+    let first = execute(program, &args, &first_input, limits);
+    if first.timed_out || !first.status.success() {
+        return first;
+    }
+    print_truncated(out, "Step 1 output:", &first.stdout);
+    let (args, second_input) = two_steps_invocation(two_steps, 2, &first.stdout);
+    let second = execute(program, &args, &second_input, limits);
+    Execution {
+        stderr: first.stderr + &second.stderr,
+        elapsed: first.elapsed + second.elapsed,
+        memory: first.memory.max(second.memory),
+        ..second
+    }
+}
+
+pub fn format_usage(elapsed: Duration, memory: Option<u64>) -> String {
+    match memory {
+        Some(memory) => format!("Elapsed: {}, Memory: {} KB", format_elapsed(elapsed), memory),
+        None => format!("Elapsed: {}", format_elapsed(elapsed)),
+    }
+}
+
+#[cfg(unix)]
+pub fn describe_status(status: ExitStatus) -> String {
+    use std::os::unix::process::ExitStatusExt;
+    match (status.code(), status.signal()) {
+        (Some(code), _) => format!("exit code {}", code),
+        (None, Some(signal)) => match signal {
+            4 => "SIGILL".to_string(),
+            6 => "SIGABRT".to_string(),
+            7 => "SIGBUS".to_string(),
+            8 => "SIGFPE".to_string(),
+            9 => "SIGKILL".to_string(),
+            11 => "SIGSEGV".to_string(),
+            _ => format!("signal {}", signal),
+        },
+        (None, None) => status.to_string(),
+    }
+}
+
+#[cfg(not(unix))]
+pub fn describe_status(status: ExitStatus) -> String {
+    match status.code() {
+        Some(code) => format!("exit code {}", code),
+        None => status.to_string(),
+    }
+}
+
+// Allocation failure messages of Rust, C++, Python and Java respectively
+fn is_allocation_failure(stderr: &str) -> bool {
+    ["memory allocation of", "std::bad_alloc", "MemoryError", "OutOfMemoryError"]
+        .iter()
+        .any(|pat| stderr.contains(pat))
+}
+
+const STDERR_MAX_LINES: usize = 20;
+const STDERR_MAX_LINE_LEN: usize = 200;
+
+// Shows the captured stderr, cut to a reasonable length
+pub fn print_stderr(out: &mut Output, stderr: &str) {
+    match stderr.split_once("stack backtrace:") {
+        Some((message, backtrace)) => {
+            print_truncated(out, "Stderr:", message);
+            print_backtrace(out, backtrace);
+        }
+        None => print_truncated(out, "Stderr:", stderr),
+    }
+}
+
+// Shows the frames of a panic backtrace that are in the solution's own code,
+// skipping the standard library and dependencies.
+fn print_backtrace(out: &mut Output, backtrace: &str) {
+    let mut frames: Vec<(&str, Option<&str>)> = vec![];
+    for line in backtrace.lines().map(|line| line.trim()) {
+        if let Some(location) = line.strip_prefix("at ") {
+            if let Some(frame) = frames.last_mut() {
+                frame.1 = Some(location);
+            }
+        } else if let Some((_, symbol)) = line.split_once(": ") {
+            frames.push((symbol, None));
+        }
+    }
+    // Paths of the crate being built are relative, unlike those of std and dependencies
+    let own_frames = frames
+        .iter()
+        .filter_map(|&(symbol, location)| Some((symbol, location?)))
+        .filter(|(_, location)| !location.starts_with('/') && !location.contains(":\\"))
+        .collect::<Vec<_>>();
+    if own_frames.is_empty() {
+        return;
+    }
+    out.eprintln(Style::new().yellow().apply_to("Backtrace:"));
+    for (symbol, location) in own_frames {
+        out.eprintln(format_args!("  {}", symbol));
+        out.eprintln(Style::new().dim().apply_to(format!("      at {}", location)));
+    }
+}
+
+fn print_truncated(out: &mut Output, title: &str, text: &str) {
+    if text.trim().is_empty() {
+        return;
+    }
+    let dim = Style::new().dim();
+    out.eprintln(Style::new().yellow().apply_to(title));
+    let lines = text.trim_end().lines().collect::<Vec<_>>();
+    for line in lines.iter().take(STDERR_MAX_LINES) {
+        match line.char_indices().nth(STDERR_MAX_LINE_LEN) {
+            Some((idx, _)) => out.eprintln(format_args!("{}...", dim.apply_to(&line[..idx]))),
+            None => out.eprintln(dim.apply_to(line)),
+        }
+    }
+    if lines.len() > STDERR_MAX_LINES {
+        out.eprintln(format_args!("... ({} more lines)", lines.len() - STDERR_MAX_LINES));
+    }
+}
+
+// Everything needed to run a single test case
+struct Runner {
+    program: Program,
+    spj: bool,
+    compare: Compare,
+    diff_style: DiffStyle,
+    checker: Option<Checker>,
+    two_steps: Option<TwoSteps>,
+    interactor: Option<String>,
+    limits: Limits,
+}
+
+impl Runner {
+    fn run(&self, input: &str, output: &str, out: &mut Output) -> Result<Outcome> {
+        match &self.interactor {
+            Some(interactor) => {
+                run_interactive(&self.program, interactor, &self.limits, input, out)
+            }
+            None => run_test_case(self, input, output, out),
+        }
+    }
+}
+
+fn run_test_case(runner: &Runner, input: &str, output: &str, out: &mut Output) -> Result<Outcome> {
+    let Runner {
+        program,
+        spj,
+        compare,
+        diff_style,
+        checker,
+        two_steps,
+        limits,
+        ..
+    } = runner;
+    let execution = match two_steps {
+        Some(two_steps) => execute_two_steps(program, two_steps, input, limits, out),
+        None => execute(program, &[], input, limits),
+    };
+    let Execution {
+        stdout: result,
+        stderr,
+        elapsed,
+        memory,
+        status,
+        timed_out,
+    } = execution;
+    let outcome = |verdict| Outcome {
+        verdict,
+        elapsed,
+        memory,
+        stdout: result.clone(),
+    };
+    if timed_out {
+        out.eprintln(format_args!(
+            "{} ({}s) on input:",
+            Style::new().red().apply_to("Time Limit Exceeded"),
+            format_elapsed(elapsed)
+        ));
+        out.eprintln(input);
+        return Ok(outcome(Verdict::TimeLimitExceeded));
+    }
+    // A program hitting the rlimit fails to allocate and dies before its peak memory gets
+    // over the limit, so treat an allocation failure under the rlimit as exceeding the limit
+    let over_limit = memory.zip(limits.memory).is_some_and(|(used, limit)| used > limit);
+    let hit_rlimit = limits.enforce_memory
+        && limits.memory.is_some()
+        && !status.success()
+        && is_allocation_failure(&stderr);
+    if over_limit || hit_rlimit {
+        out.eprintln(format_args!(
+            "{} ({}) on input:",
+            Style::new().red().apply_to("Memory Limit Exceeded"),
+            format_usage(elapsed, memory)
+        ));
+        out.eprintln(input);
+        print_stderr(out, &stderr);
+        return Ok(outcome(Verdict::MemoryLimitExceeded));
+    }
+    // A panic in a spawned thread does not necessarily make the process fail
+    if !status.success() || stderr.contains("panicked at") {
+        out.eprintln(format_args!(
+            "{} ({}) on input:",
+            Style::new().red().apply_to("Runtime Error"),
+            describe_status(status)
+        ));
+        out.eprintln(input);
+        print_stderr(out, &stderr);
+        return Ok(outcome(Verdict::RuntimeError));
+    }
+
+    // A checker gives a definite verdict even on Special Judge problems
+    let check = checker
+        .as_ref()
+        .map(|checker| checker.check(input, output, &result))
+        .transpose()?;
+    let (failed, definite) = match &check {
+        Some(check) => (!check.passed, true),
+        None => (!compare.matches(&result, output), !*spj),
+    };
+    compare.print_diff(out, &result, output, definite && failed, *diff_style);
+    if let Some(message) = check.as_ref().and_then(|check| check.message.as_ref()) {
+        out.println(format_args!("Checker: {}", message));
+    }
+    if definite && failed {
+        out.eprintln(format_args!("{} on input:", Style::new().red().apply_to("Test failed")));
+        out.eprintln(input);
+        print_stderr(out, &stderr);
+        return Ok(outcome(Verdict::WrongAnswer));
+    }
+    print_stderr(out, &stderr);
+    out.println(format_usage(elapsed, memory));
     if !failed {
-        Ok(())
+        Ok(outcome(Verdict::Accepted))
     } else {
-        Err("")?
+        Ok(outcome(Verdict::Unknown))
+    }
+}
+
+pub type CaseResult = std::result::Result<Outcome, String>;
+
+// Runs the cases on `jobs` threads and passes each case's index, output and result to `report`,
+// in the order of the cases. Stops running further cases once `report` returns false.
+fn run_cases(
+    runner: &Runner,
+    cases: &[TestCase],
+    jobs: usize,
+    mut report: impl FnMut(usize, Output, CaseResult) -> bool,
+) {
+    let next = AtomicUsize::new(0);
+    let stop = AtomicBool::new(false);
+    let (tx, rx) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..jobs.max(1) {
+            let tx = tx.clone();
+            let (next, stop) = (&next, &stop);
+            scope.spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(case) = cases.get(i) else {
+                        break;
+                    };
+                    let mut out = Output::default();
+                    out.println(Style::new().bold().apply_to(case.label()));
+                    let result = runner
+                        .run(&case.input, &case.output, &mut out)
+                        .map_err(|e| e.to_string());
+                    if tx.send((i, out, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(tx);
+        // Results arrive in completion order; hold them until all earlier cases are reported
+        let mut pending = BTreeMap::new();
+        let mut next_report = 0;
+        for (i, out, result) in rx {
+            pending.insert(i, (out, result));
+            while let Some((out, result)) = pending.remove(&next_report) {
+                next_report += 1;
+                if !stop.load(Ordering::Relaxed) && !report(next_report - 1, out, result) {
+                    stop.store(true, Ordering::Relaxed);
+                }
+            }
+        }
+    });
+}
+
+// The bin built from src/main.rs, or else src/bin/main.rs, of the current package
+pub fn default_bin() -> Result<String> {
+    let package = CurrentPackage::load()?;
+    match ["src/main.rs", "src/bin/main.rs"]
+        .into_iter()
+        .find_map(|path| package.bin_at(path))
+    {
+        Some(bin) => Ok(bin.name.clone()),
+        None => Err("Error: Neither src/main.rs nor src/bin/main.rs is present. Please specify --bin flag.")?,
+    }
+}
+
+// Finds the source file of a bin in the current package
+pub fn bin_source(bin: &str) -> Result<PathBuf> {
+    match CurrentPackage::load()?.bin_named(bin) {
+        Some(bin) => Ok(bin.src_path.clone()),
+        None => Err(format!("Error: could not find the source file of bin `{}`. Please specify --path flag.", bin))?,
     }
 }
 
-fn default_bin() -> Result<String> {
-    let src_main = "src/main.rs".parse::<PathBuf>().unwrap();
-    if src_main.exists() {
-        let current_dir = std::env::current_dir()?;
-        let x = current_dir.file_name().unwrap();
-        return Ok(x.to_str().unwrap().to_owned());
+// Builds the program for a source file given with --path.
+fn path_program(path: &Path, language: Option<LanguageType>, build: &Build) -> Result<Program> {
+    if let Some(profile) = language::find_profile(path, language)? {
+        return language::build(path, &profile);
     }
-    let src_bin_main = "src/bin/main.rs".parse::<PathBuf>().unwrap();
-    if src_bin_main.exists() {
-        return Ok("main".to_owned());
+    let Ok(source) = path.canonicalize() else {
+        Err(format!("Error: {} not found.", path.display()))?
+    };
+    let bin = CurrentPackage::load()
+        .ok()
+        .and_then(|package| package.bin_at(&source).map(|bin| bin.name.clone()));
+    match bin {
+        Some(bin) => build_program(&BinOrCmd::Bin(bin), build),
+        None => Err(format!(
+            "Error: no language profile for {}. Pass --lang, or add a profile to {}.",
+            path.display(),
+            LanguageProfiles::file().display()
+        ))?,
     }
-    Err("Error: Neither src/main.rs nor src/bin/main.rs is present. Please specify --bin flag.")?
 }
 
-pub fn test(problem_id: &str, bin_or_cmd: Option<BinOrCmd>, spj_prompt: bool, refresh: bool) -> Result<()> {
-    let bin_or_cmd = match bin_or_cmd {
-        Some(inner) => inner,
-        None => {
-            let bin = default_bin()?;
-            BinOrCmd::Bin(bin)
+pub fn test(opts: Test) -> Result<()> {
+    if opts.watch {
+        return watch::watch(opts);
+    }
+    let Test {
+        problem_id,
+        bin_or_cmd,
+        spj_prompt,
+        refresh,
+        time_factor,
+        memory_rlimit,
+        checker,
+        interactor,
+        two_steps,
+        jobs,
+        compare,
+        diff_style,
+        format,
+        keep_going,
+        build,
+        path,
+        language,
+        boj_env,
+        boj_toolchain,
+        no_bundle,
+        ..
+    } = opts;
+    let keep_going = match keep_going {
+        Some(keep_going) => keep_going,
+        None => Settings::load()?.keep_going,
+    };
+    let program = match (bin_or_cmd, path) {
+        (bin_or_cmd, path) if boj_env => {
+            let source = match (path, &bin_or_cmd) {
+                (Some(path), _) => PathBuf::from(path),
+                (None, Some(BinOrCmd::Bin(bin))) => bin_source(bin)?,
+                (None, None) => bin_source(&default_bin()?)?,
+                (None, Some(BinOrCmd::Cmd(_))) => Err("Error: --boj-env with --cmd needs --path to the source file.")?,
+            };
+            // The source is compiled alone as submitted: bundled with its modules and local crates
+            let source = if no_bundle { source } else { bundled_source(&source)? };
+            Program::Exe {
+                path: bojenv::build(&source, get_language_id(language), boj_toolchain)?,
+                backtrace: false,
+            }
         }
+        // A source file on its own is built with its language profile, or with cargo if it is a bin in the package
+        (None, Some(path)) => path_program(Path::new(&path), language, &build)?,
+        (Some(bin_or_cmd), _) => build_program(&bin_or_cmd, &build)?,
+        (None, None) => build_program(&BinOrCmd::Bin(default_bin()?), &build)?,
     };
-    if let BinOrCmd::Bin(ref bin) = bin_or_cmd {
-        precompile_bin(bin)?;
+    let mut data = ProblemData::load(&problem_id, refresh);
+    // A comparison mode given on the command line is remembered for the problem
+    if compare.is_some() && compare != data.compare {
+        data.compare = compare;
+        data.save(&problem_id);
     }
-    let ProblemData { spj, testcases } = ProblemData::load(problem_id, refresh);
-    let mut failed = false;
-    for (input, output) in &testcases {
-        let result = run_test_case(&bin_or_cmd, spj, input, output);
-        failed = failed || result.is_err();
-        if !spj { result?; }
+    let ProblemData {
+        spj,
+        two_steps: is_two_steps,
+        time_limit,
+        memory_limit,
+        testcases,
+        compare,
+    } = data;
+    let compare = compare.unwrap_or(Compare::LineTrim);
+    // Two-step problems are run in two steps even without the flag, feeding the output as is
+    let two_steps = two_steps.or(is_two_steps.then_some(TwoSteps::Plain));
+    let limits = Limits {
+        time: time_limit.map(|limit| limit.mul_f64(time_factor)),
+        memory: memory_limit,
+        enforce_memory: memory_rlimit,
+        jobs,
+    };
+    // Custom cases are kept separately from the cache, so they survive --refresh
+    let samples = testcases
+        .into_iter()
+        .enumerate()
+        .map(|(i, (input, output))| TestCase {
+            source: Source::Sample,
+            id: (i + 1).to_string(),
+            input,
+            output,
+        });
+    let customs = CustomCase::load_all(&problem_id)
+        .into_iter()
+        .map(|case| TestCase {
+            source: Source::Custom,
+            id: case.name,
+            input: case.input,
+            output: case.output,
+        });
+    let mut cases = samples.chain(customs).collect::<Vec<_>>();
+    // Interactive problems have no samples, but can still be run once with an empty input
+    if interactor.is_some() && cases.is_empty() {
+        cases.push(TestCase {
+            source: Source::Interactive,
+            id: String::new(),
+            input: String::new(),
+            output: String::new(),
+        });
+    }
+    let runner = Runner {
+        program,
+        spj,
+        compare,
+        diff_style,
+        checker,
+        two_steps,
+        interactor,
+        limits,
+    };
+    let mut records = vec![];
+    run_cases(&runner, &cases, jobs, |i, out, result| {
+        // Machine-readable formats replace the human-oriented output entirely
+        if format == Format::Text {
+            out.flush();
+        }
+        let record = CaseRecord::new(&cases[i], Some(result));
+        let proceed = keep_going || !record.failed();
+        records.push(record);
+        proceed
+    });
+    // Cases not run after a failure are reported as skipped
+    for case in &cases[records.len()..] {
+        records.push(CaseRecord::new(case, None));
     }
+    match format {
+        Format::Text if keep_going => report::print_summary(&records),
+        Format::Text => {}
+        Format::Json => report::print_json(&problem_id, &records)?,
+        Format::Junit => report::print_junit(&problem_id, &records),
+    }
+    // The first error is shown; the verdicts are already shown with each case
+    if let Some(record) = records.iter().find(|r| r.failed()) {
+        Err(record.error.clone().unwrap_or_default())?
+    }
+    let failed = records.iter().any(|r| r.verdict == Some(Verdict::Unknown));
     if spj && spj_prompt && failed {
+        // Nobody is there to answer the prompt, so an unjudged output counts as a failure
+        if format != Format::Text {
+            Err("")?
+        }
         print!("Press Enter to proceed, any other key to abort:");
         let mut stdout = std::io::stdout();
         stdout.flush()?;
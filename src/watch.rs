@@ -0,0 +1,128 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use console::Style;
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use crossterm::{cursor, execute, terminal};
+
+use crate::optparse::{BinOrCmd, Submit, Test};
+use crate::submit;
+use crate::test::{bin_source, default_bin, test};
+use crate::Result;
+
+// How often the source file is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+enum Action {
+    Rerun,
+    Submit,
+    Quit,
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+// Waits until the source file changes or a key is pressed.
+fn wait(source: &Path, last_modified: Option<SystemTime>, can_submit: bool) -> Result<Action> {
+    terminal::enable_raw_mode()?;
+    let action = loop {
+        if modified(source) != last_modified {
+            break Action::Rerun;
+        }
+        if !event::poll(POLL_INTERVAL)? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                break Action::Quit
+            }
+            KeyCode::Char('q') | KeyCode::Esc => break Action::Quit,
+            KeyCode::Char('r') | KeyCode::Enter => break Action::Rerun,
+            KeyCode::Char('s') if can_submit => break Action::Submit,
+            _ => {}
+        }
+    };
+    terminal::disable_raw_mode()?;
+    Ok(action)
+}
+
+// Re-runs the tests whenever the solution's source file changes, until the user quits or submits.
+pub fn watch(opts: Test) -> Result<()> {
+    let source = match (&opts.path, &opts.bin_or_cmd) {
+        (Some(path), _) => PathBuf::from(path),
+        (None, Some(BinOrCmd::Bin(bin))) => bin_source(bin)?,
+        (None, None) => bin_source(&default_bin()?)?,
+        (None, Some(BinOrCmd::Cmd(_))) => {
+            Err("Error: --watch with --cmd needs --path to the source file to watch.")?
+        }
+    };
+    // The language of a --cmd solution is unknown unless given with --lang
+    let submittable =
+        opts.language.is_some() || !matches!(opts.bin_or_cmd, Some(BinOrCmd::Cmd(_)));
+    let mut opts = Test {
+        watch: false,
+        // Nobody is there to answer the prompt in between runs
+        spj_prompt: false,
+        ..opts
+    };
+    let mut stdout = std::io::stdout();
+    loop {
+        // Changes made while the tests run trigger another run right away
+        let last_modified = modified(&source);
+        execute!(
+            stdout,
+            terminal::Clear(terminal::ClearType::All),
+            cursor::MoveTo(0, 0)
+        )?;
+        println!(
+            "{}",
+            Style::new()
+                .dim()
+                .apply_to(format!("Watching {} for changes", source.display()))
+        );
+        let passed = match test(opts.clone()) {
+            Ok(()) => true,
+            Err(e) => {
+                let message = e.to_string();
+                if !message.is_empty() {
+                    eprintln!("{}", message);
+                }
+                false
+            }
+        };
+        // The cache only needs to be refreshed once
+        opts.refresh = false;
+        let can_submit = passed && submittable;
+        let keys = if can_submit {
+            "Press s to submit, r to re-run, q to quit."
+        } else {
+            "Press r to re-run, q to quit."
+        };
+        println!();
+        print!("{}", Style::new().dim().apply_to(keys));
+        stdout.flush()?;
+        let action = wait(&source, last_modified, can_submit)?;
+        println!();
+        match action {
+            Action::Rerun => {}
+            Action::Quit => return Ok(()),
+            Action::Submit => {
+                return submit::submit(Submit {
+                    problem_id: opts.problem_id,
+                    path: Some(source.display().to_string()),
+                    language: opts.language,
+                    code_open: None,
+                    no_bundle: false,
+                    // The tests have just passed
+                    test_first: Some(false),
+                })
+            }
+        }
+    }
+}